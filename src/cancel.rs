@@ -0,0 +1,218 @@
+//! Cancellation broadcast by closing a pipe.
+//!
+//! See [`CancelSource`].
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+use mio::{event, Interest, Registry, Token};
+
+use crate::process::Inheritable;
+use crate::{new_pipe, sys, Receiver, Sender};
+
+/// Source of cancellation.
+///
+/// The `CancelSource` holds the only sending end of a pipe, while every
+/// [`CancelToken`] holds a receiving end of the same pipe. Once the sending end
+/// is closed, either by calling [`CancelSource::cancel`], dropping the
+/// `CancelSource` or the process exiting, all tokens are cancelled. This
+/// uses the same mechanism as detecting a dropped [`Sender`] described in
+/// [`new_pipe`].
+///
+/// Tokens can be passed to other processes, e.g. using
+/// [`CancelToken::into_inheritable`] and [`CancelToken::from_env`], which
+/// makes it possible to detect the exit of a parent process without polling.
+///
+/// [`new_pipe`]: crate::new_pipe
+///
+/// # Notes
+///
+/// The sending end has `FD_CLOEXEC` set, so it's not inherited by child
+/// processes started using `exec(2)`. However if the process forks without
+/// executing another program the `CancelSource` must be dropped in the child
+/// process, otherwise the tokens are only cancelled once both processes closed
+/// the sending end.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+///
+/// use mio_pipe::cancel::CancelSource;
+///
+/// # fn main() -> io::Result<()> {
+/// let source = CancelSource::new()?;
+/// let token = source.token()?;
+/// assert!(!token.is_cancelled()?);
+///
+/// source.cancel();
+/// assert!(token.is_cancelled()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CancelSource {
+    sender: Sender,
+    /// Used to create new tokens.
+    receiver: Receiver,
+}
+
+impl CancelSource {
+    /// Create a new `CancelSource`.
+    pub fn new() -> io::Result<CancelSource> {
+        let (sender, receiver) = new_pipe()?;
+        Ok(CancelSource { sender, receiver })
+    }
+
+    /// Create a new [`CancelToken`] for this source.
+    pub fn token(&self) -> io::Result<CancelToken> {
+        self.receiver
+            .try_clone()
+            .map(|receiver| CancelToken { receiver })
+    }
+
+    /// Cancel all tokens.
+    ///
+    /// This is the same as dropping the `CancelSource`.
+    pub fn cancel(self) {
+        drop(self)
+    }
+}
+
+impl AsRawFd for CancelSource {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sender.as_raw_fd()
+    }
+}
+
+/// Token to detect cancellation, see [`CancelSource`].
+///
+/// The `CancelToken` can be registered with [`READABLE`] interest, once
+/// cancelled [`is_read_closed`] will return true for its events.
+///
+/// Tokens of the same source share the underlying pipe, see
+/// [`CancelToken::into_inheritable`] for how passing a token to a child
+/// process affects the other tokens.
+///
+/// [`READABLE`]: Interest::READABLE
+/// [`is_read_closed`]: mio::event::Event::is_read_closed
+#[derive(Debug)]
+pub struct CancelToken {
+    receiver: Receiver,
+}
+
+impl CancelToken {
+    /// Create a `CancelToken` from the file descriptor in the environment
+    /// variable `key`, removing the variable.
+    ///
+    /// See [`Receiver::take_from_env`].
+    ///
+    /// # Safety
+    ///
+    /// The `CancelToken` takes ownership of the file descriptor, see
+    /// [`Receiver::from_env`].
+    pub unsafe fn from_env<K: AsRef<std::ffi::OsStr>>(key: K) -> io::Result<CancelToken> {
+        Receiver::take_from_env(key).map(CancelToken::from)
+    }
+
+    /// Returns `true` if the token is cancelled.
+    pub fn is_cancelled(&self) -> io::Result<bool> {
+        self.wait(Some(Duration::from_secs(0)))
+    }
+
+    /// Wait until the token is cancelled, or the `timeout` elapsed.
+    ///
+    /// Returns `true` if the token is cancelled.
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let mut fds = [libc::pollfd {
+            fd: self.receiver.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        sys::poll(&mut fds, timeout).map(|n| n != 0 && fds[0].revents & libc::POLLHUP != 0)
+    }
+
+    /// Duplicate the `CancelToken`.
+    pub fn try_clone(&self) -> io::Result<CancelToken> {
+        self.receiver
+            .try_clone()
+            .map(|receiver| CancelToken { receiver })
+    }
+
+    /// Convert the token so it can be passed to a child process, see
+    /// [`Receiver::into_inheritable`].
+    ///
+    /// # Notes
+    ///
+    /// The returned pipe end is in blocking mode. On Linux and Android the
+    /// pipe is reopened using `/proc/self/fd`, so this doesn't affect other
+    /// tokens. On other platforms tokens created by [`CancelSource::token`]
+    /// or [`CancelToken::try_clone`] share the file status flags, meaning
+    /// this also sets all other tokens of the same source into blocking
+    /// mode. This doesn't affect [`CancelToken::is_cancelled`],
+    /// [`CancelToken::wait`] or using the token with [`Poll`], as these don't
+    /// read from the pipe.
+    ///
+    /// [`Poll`]: mio::Poll
+    pub fn into_inheritable(self) -> io::Result<Inheritable> {
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            // Reopening the pipe creates a new open file description, which
+            // has its own file status flags. Open in non-blocking mode, as
+            // opening the read end of a pipe without writers (i.e. when
+            // cancelled) blocks otherwise.
+            use std::fs::OpenOptions;
+            use std::os::unix::fs::OpenOptionsExt;
+
+            let path = format!("/proc/self/fd/{}", self.receiver.as_raw_fd());
+            let file = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)?;
+            Inheritable::new(file)
+        }
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        {
+            self.receiver.into_inheritable()
+        }
+    }
+}
+
+impl From<Receiver> for CancelToken {
+    /// Create a `CancelToken` from the receiving end of a pipe, it's cancelled
+    /// once all sending ends are closed.
+    fn from(receiver: Receiver) -> CancelToken {
+        CancelToken { receiver }
+    }
+}
+
+impl event::Source for CancelToken {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.receiver.deregister(registry)
+    }
+}
+
+impl AsRawFd for CancelToken {
+    fn as_raw_fd(&self) -> RawFd {
+        self.receiver.as_raw_fd()
+    }
+}
@@ -0,0 +1,422 @@
+//! Bounded multi-producer, single-consumer channel.
+//!
+//! See [`bounded`].
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use mio::{event, Interest, Registry, Token};
+
+use crate::new_pipe;
+
+/// Create a new bounded channel with room for `capacity` values.
+///
+/// The values are send using an in-memory queue, while a pair of Unix pipes
+/// provide the readiness events.
+///
+/// # Events
+///
+/// The [`Receiver`] can be registered with [`READABLE`] interest, it will
+/// receive a readable event once values are queued. Similar to [`new_pipe`],
+/// once all `Sender`s are dropped [`is_read_closed`] will return true for
+/// events of the `Receiver`.
+///
+/// Each [`Sender`] can be registered with [`WRITABLE`] interest, it will
+/// receive a writable event once space is available after [`try_send`]
+/// returned [`TrySendError::Full`]. Once the `Receiver` is dropped
+/// [`is_write_closed`] will return true for events of the `Sender`.
+///
+/// Events are only generated for changes in state, i.e. after
+/// [`Receiver::try_recv`] returned [`TryRecvError::Empty`] or
+/// [`Sender::try_send`] returned [`TrySendError::Full`].
+///
+/// [`new_pipe`]: crate::new_pipe
+/// [`READABLE`]: Interest::READABLE
+/// [`WRITABLE`]: Interest::WRITABLE
+/// [`is_read_closed`]: mio::event::Event::is_read_closed
+/// [`is_write_closed`]: mio::event::Event::is_write_closed
+/// [`try_send`]: Sender::try_send
+///
+/// # Panics
+///
+/// This will panic if `capacity` is zero.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::thread;
+///
+/// use mio::{Events, Interest, Poll, Token};
+/// use mio_pipe::channel::{self, TryRecvError};
+///
+/// const RECEIVER: Token = Token(0);
+///
+/// # fn main() -> io::Result<()> {
+/// let mut poll = Poll::new()?;
+/// let mut events = Events::with_capacity(8);
+///
+/// let (sender, mut receiver) = channel::bounded(4)?;
+/// poll.registry().register(&mut receiver, RECEIVER, Interest::READABLE)?;
+///
+/// let handle = thread::spawn(move || sender.try_send("Hello world").unwrap());
+///
+/// loop {
+///     match receiver.try_recv() {
+///         Ok(msg) => {
+///             assert_eq!(msg, "Hello world");
+///             break;
+///         }
+///         Err(TryRecvError::Empty) => poll.poll(&mut events, None)?,
+///         Err(TryRecvError::Disconnected) => unreachable!(),
+///     }
+/// }
+/// # handle.join().unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub fn bounded<T>(capacity: usize) -> io::Result<(Sender<T>, Receiver<T>)> {
+    assert!(capacity > 0, "can't create a channel with zero capacity");
+
+    let (data_sender, data_receiver) = new_pipe()?;
+    let (space_sender, space_receiver) = new_pipe()?;
+    // Makes filling the pipe cheaper, see `fill_space`.
+    space_sender.shrink_buffer();
+
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            capacity,
+            senders: 1,
+            receiver_alive: true,
+            receiver_waiting: false,
+            senders_waiting: false,
+        }),
+    });
+    let sender = Sender {
+        shared: shared.clone(),
+        data: Arc::new(data_sender),
+        space: space_sender,
+    };
+    let receiver = Receiver {
+        shared,
+        data: data_receiver,
+        space: space_receiver,
+    };
+    Ok((sender, receiver))
+}
+
+/// Sending side of the channel, see [`bounded`].
+///
+/// The `Sender` can be cloned to send from multiple threads.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+    /// Write end of the pipe used to notify the `Receiver`.
+    data: Arc<crate::Sender>,
+    /// Write end of the pipe that is filled once the channel is full. Each
+    /// `Sender` has it's own (duplicated) file descriptor so it can be
+    /// registered separately.
+    space: crate::Sender,
+}
+
+impl<T> Sender<T> {
+    /// Attempt to send `value` into the channel.
+    ///
+    /// If the channel is full this returns [`TrySendError::Full`], after which
+    /// a writable event is generated once space is available.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut state = self.shared.lock();
+        if !state.receiver_alive {
+            return Err(TrySendError::Disconnected(value));
+        }
+
+        if state.queue.len() >= state.capacity {
+            if !state.senders_waiting {
+                state.senders_waiting = true;
+                fill_space(&self.space);
+            }
+            return Err(TrySendError::Full(value));
+        }
+
+        state.queue.push_back(value);
+        if state.receiver_waiting {
+            state.receiver_waiting = false;
+            // If this fails the pipe is full, in which case the `Receiver` is
+            // notified already.
+            let _ = (&*self.data).write(&[1]);
+        }
+        Ok(())
+    }
+
+    /// Returns the capacity of the channel.
+    pub fn capacity(&self) -> usize {
+        self.shared.lock().capacity
+    }
+
+    /// Duplicates the `Sender`, returning an error if the file descriptor
+    /// couldn't be duplicated.
+    ///
+    /// This is the fallible version of `Clone::clone`.
+    pub fn try_clone(&self) -> io::Result<Sender<T>> {
+        let space = self.space.try_clone()?;
+        self.shared.lock().senders += 1;
+        Ok(Sender {
+            shared: self.shared.clone(),
+            data: self.data.clone(),
+            space,
+        })
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    /// # Panics
+    ///
+    /// This panics if the file descriptor can't be duplicated, see
+    /// [`Sender::try_clone`] for a version that doesn't panic.
+    fn clone(&self) -> Sender<T> {
+        self.try_clone().expect("failed to clone `channel::Sender`")
+    }
+}
+
+impl<T> event::Source for Sender<T> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.space.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.space.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.space.deregister(registry)
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("data", &self.data)
+            .field("space", &self.space)
+            .finish()
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // Once the last `Sender` is dropped the write end of the `data` pipe is
+        // closed, generating an event for the `Receiver`.
+        self.shared.lock().senders -= 1;
+    }
+}
+
+/// Receiving side of the channel, see [`bounded`].
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    /// Read end of the pipe used to notify the `Receiver`.
+    data: crate::Receiver,
+    /// Read end of the pipe that is drained once space is available.
+    space: crate::Receiver,
+}
+
+impl<T> Receiver<T> {
+    /// Attempt to receive a value from the channel.
+    ///
+    /// If the channel is empty this returns [`TryRecvError::Empty`], after
+    /// which a readable event is generated once a value is send.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.lock();
+        if let Some(value) = state.queue.pop_front() {
+            if state.senders_waiting {
+                state.senders_waiting = false;
+                drain(&mut self.space);
+            }
+            Ok(value)
+        } else if state.senders == 0 {
+            Err(TryRecvError::Disconnected)
+        } else {
+            if !state.receiver_waiting {
+                state.receiver_waiting = true;
+                drain(&mut self.data);
+            }
+            Err(TryRecvError::Empty)
+        }
+    }
+
+    /// Returns the number of values in the channel.
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Returns `true` if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shared.lock().queue.is_empty()
+    }
+}
+
+impl<T> event::Source for Receiver<T> {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.data.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.data.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.data.deregister(registry)
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("data", &self.data)
+            .field("space", &self.space)
+            .finish()
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        // Dropping the read end of the `space` pipe will generate an event for
+        // all `Sender`s.
+        self.shared.lock().receiver_alive = false;
+    }
+}
+
+/// Error returned by [`Sender::try_send`].
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum TrySendError<T> {
+    /// The channel is full.
+    Full(T),
+    /// The [`Receiver`] was dropped.
+    Disconnected(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Returns the value that couldn't be send.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(value) | TrySendError::Disconnected(value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(..) => f.write_str("Full(..)"),
+            TrySendError::Disconnected(..) => f.write_str("Disconnected(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(..) => f.write_str("sending on a full channel"),
+            TrySendError::Disconnected(..) => f.write_str("sending on a closed channel"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+/// Error returned by [`Receiver::try_recv`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TryRecvError {
+    /// The channel is empty.
+    Empty,
+    /// All [`Sender`]s were dropped and the channel is empty.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Disconnected => f.write_str("receiving on a closed channel"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // The state is always valid, even if another thread panicked.
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// State of the channel.
+///
+/// All reads and writes of the pipes are done while holding the lock, which
+/// prevents lost wake-ups.
+struct State<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    /// Number of alive `Sender`s.
+    senders: usize,
+    receiver_alive: bool,
+    /// True if the `Receiver` got an empty channel and is waiting for a value.
+    receiver_waiting: bool,
+    /// True if a `Sender` got a full channel and the `space` pipe is filled.
+    senders_waiting: bool,
+}
+
+/// Fill the pipe, making the write end not writable.
+fn fill_space(mut sender: &crate::Sender) {
+    let buf = [0; 4096];
+    loop {
+        match sender.write(&buf) {
+            Ok(_) => continue,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            // Either the pipe is full (`WouldBlock`), or an error we can't
+            // handle.
+            Err(_) => break,
+        }
+    }
+}
+
+/// Drain the pipe, making the write end writable (again).
+fn drain(receiver: &mut crate::Receiver) {
+    let mut buf = [0; 4096];
+    loop {
+        match receiver.read(&mut buf) {
+            Ok(0) => break,
+            Ok(_) => continue,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            // Either the pipe is empty (`WouldBlock`), or an error we can't
+            // handle.
+            Err(_) => break,
+        }
+    }
+}
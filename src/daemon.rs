@@ -0,0 +1,167 @@
+//! Daemonization.
+//!
+//! See [`daemonize`].
+
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process;
+
+use crate::{new_pipe, Sender};
+
+/// Exit code used by the original process if the daemon didn't report its
+/// status.
+const NO_STATUS_EXIT_CODE: i32 = 1;
+
+/// Turn the current process into a daemon.
+///
+/// This forks twice, with a call to `setsid(2)` in between to detach from the
+/// controlling terminal, and redirects standard in, out and error to
+/// `/dev/null`. This function only returns in the daemon process.
+///
+/// The original process waits until the daemon reports its status using the
+/// returned [`Daemon`], after which it exits with the matching exit code. This
+/// way errors during initialisation (after forking) can still be reported to
+/// the user that started the daemon. Communication is done using a pipe (see
+/// [`new_pipe`]) with `FD_CLOEXEC` set, if the daemon exits, or executes
+/// another program, without reporting its status it's considered an error.
+///
+/// Note that this doesn't change the working directory or umask.
+///
+/// [`new_pipe`]: crate::new_pipe
+///
+/// # Safety
+///
+/// This must be called while the process is single threaded, as only the
+/// calling thread is copied into the daemon process.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io;
+///
+/// use mio_pipe::daemon::daemonize;
+///
+/// # fn initialise() -> io::Result<()> { Ok(()) }
+/// # fn main() -> io::Result<()> {
+/// let daemon = unsafe { daemonize()? };
+///
+/// // Only the daemon process gets here.
+/// match initialise() {
+///     Ok(()) => daemon.report_ok()?,
+///     Err(err) => {
+///         let msg = format!("failed to initialise: {}", err);
+///         daemon.report_error(1, &msg)?;
+///         return Err(err);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub unsafe fn daemonize() -> io::Result<Daemon> {
+    let (sender, mut receiver) = new_pipe()?;
+    sender.set_nonblocking(false)?;
+    receiver.set_nonblocking(false)?;
+
+    match libc::fork() {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        pid => {
+            // Original process, wait for the daemon's status.
+            drop(sender);
+            let mut buf = Vec::new();
+            let res = receiver.read_to_end(&mut buf);
+            // Reap the intermediate process.
+            let mut status = 0;
+            let _ = libc::waitpid(pid, &mut status, 0);
+
+            let code = match (res, buf.split_first()) {
+                (Ok(_), Some((0, _))) => 0,
+                (Ok(_), Some((code, msg))) => {
+                    let _ = io::stderr().write_all(msg);
+                    i32::from(*code)
+                }
+                (Ok(_), None) => {
+                    let _ = io::stderr().write_all(b"daemon exited without reporting its status\n");
+                    NO_STATUS_EXIT_CODE
+                }
+                (Err(err), _) => {
+                    let msg = format!("failed to read status of daemon: {}\n", err);
+                    let _ = io::stderr().write_all(msg.as_bytes());
+                    NO_STATUS_EXIT_CODE
+                }
+            };
+            process::exit(code)
+        }
+    }
+
+    // Intermediate process.
+    drop(receiver);
+    let fd = sender.as_raw_fd();
+    if libc::setsid() == -1 {
+        fail(fd, "setsid")
+    }
+    match libc::fork() {
+        -1 => fail(fd, "fork"),
+        0 => {}
+        _ => libc::_exit(0),
+    }
+
+    // Daemon process.
+    let null = libc::open(b"/dev/null\0".as_ptr().cast(), libc::O_RDWR);
+    if null == -1 {
+        fail(fd, "open /dev/null")
+    }
+    for target in 0..=2 {
+        if libc::dup2(null, target) == -1 {
+            fail(fd, "dup2")
+        }
+    }
+    if null > 2 {
+        let _ = libc::close(null);
+    }
+
+    Ok(Daemon { sender })
+}
+
+/// Report the last OS error to the original process and exit.
+unsafe fn fail(fd: RawFd, what: &str) -> ! {
+    let err = io::Error::last_os_error();
+    let msg = format!("failed to daemonize: {}: {}\n", what, err);
+    let mut buf = Vec::with_capacity(1 + msg.len());
+    buf.push(NO_STATUS_EXIT_CODE as u8);
+    buf.extend_from_slice(msg.as_bytes());
+    let _ = libc::write(fd, buf.as_ptr().cast(), buf.len());
+    libc::_exit(NO_STATUS_EXIT_CODE)
+}
+
+/// Handle to report the status of the daemon to the original process, see
+/// [`daemonize`].
+///
+/// Dropping the `Daemon` without reporting its status is considered an error.
+#[derive(Debug)]
+pub struct Daemon {
+    sender: Sender,
+}
+
+impl Daemon {
+    /// Report that the daemon is initialised, the original process will exit
+    /// successfully.
+    pub fn report_ok(mut self) -> io::Result<()> {
+        self.sender.write_all(&[0])
+    }
+
+    /// Report that the daemon failed to initialise, the original process will
+    /// print `msg` to standard error and exit with `code`.
+    ///
+    /// If `code` is zero an exit code of one is used.
+    pub fn report_error(mut self, code: u8, msg: &str) -> io::Result<()> {
+        let code = if code == 0 { 1 } else { code };
+        let mut buf = Vec::with_capacity(2 + msg.len());
+        buf.push(code);
+        buf.extend_from_slice(msg.as_bytes());
+        if !msg.ends_with('\n') {
+            buf.push(b'\n');
+        }
+        self.sender.write_all(&buf)
+    }
+}
@@ -0,0 +1,264 @@
+//! Bidirectional pipes.
+//!
+//! See [`new_duplex`].
+
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+
+use mio::event::{self, Source};
+use mio::{Interest, Registry, Token};
+
+use crate::{new_pipe, Receiver, Sender};
+
+/// Create a pair of connected, bidirectional pipe ends.
+///
+/// This creates two pipes using [`new_pipe`], data written to one
+/// `DuplexPipe` can be read from the other and vice versa. Like the ends
+/// returned by `new_pipe` both are in non-blocking mode and have `FD_CLOEXEC`
+/// set.
+///
+/// [`new_pipe`]: crate::new_pipe
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Read, Write};
+///
+/// use mio_pipe::duplex::new_duplex;
+///
+/// # fn main() -> io::Result<()> {
+/// let (mut parent, mut child) = new_duplex()?;
+///
+/// parent.write_all(b"ping")?;
+/// let mut buf = [0; 4];
+/// child.read_exact(&mut buf)?;
+/// assert_eq!(&buf, b"ping");
+///
+/// child.write_all(b"pong")?;
+/// parent.read_exact(&mut buf)?;
+/// assert_eq!(&buf, b"pong");
+///
+/// // Half-close, the other end reads end of file.
+/// parent.close_write();
+/// assert_eq!(child.read(&mut buf)?, 0);
+/// # Ok(())
+/// # }
+/// ```
+pub fn new_duplex() -> io::Result<(DuplexPipe, DuplexPipe)> {
+    let (sender1, receiver1) = new_pipe()?;
+    let (sender2, receiver2) = new_pipe()?;
+    Ok((
+        DuplexPipe::new(sender1, receiver2),
+        DuplexPipe::new(sender2, receiver1),
+    ))
+}
+
+/// One end of a bidirectional pipe, see [`new_duplex`].
+///
+/// # Registering
+///
+/// `DuplexPipe` registers both its receiving and sending end using the same
+/// token. Events with [`READABLE`] interest come from the receiving end,
+/// events with [`WRITABLE`] interest from the sending end.
+///
+/// [`READABLE`]: Interest::READABLE
+/// [`WRITABLE`]: Interest::WRITABLE
+#[derive(Debug)]
+pub struct DuplexPipe {
+    /// `None` after `close_write` is called.
+    sender: Option<Sender>,
+    receiver: Receiver,
+    /// Whether the receiving end is registered.
+    receiver_registered: bool,
+    /// Whether the sending end is registered.
+    sender_registered: bool,
+}
+
+impl DuplexPipe {
+    /// Create a `DuplexPipe` from a `sender` and `receiver`, which are usually
+    /// ends of different pipes.
+    pub fn new(sender: Sender, receiver: Receiver) -> DuplexPipe {
+        DuplexPipe {
+            sender: Some(sender),
+            receiver,
+            receiver_registered: false,
+            sender_registered: false,
+        }
+    }
+
+    /// Set both ends in non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.receiver.set_nonblocking(nonblocking)?;
+        match &self.sender {
+            Some(sender) => sender.set_nonblocking(nonblocking),
+            None => Ok(()),
+        }
+    }
+
+    /// Close the sending end, leaving the receiving end open.
+    ///
+    /// Once closed the other end reads end of file and writing to this end
+    /// returns a [`BrokenPipe`] error.
+    ///
+    /// [`BrokenPipe`]: io::ErrorKind::BrokenPipe
+    pub fn close_write(&mut self) {
+        // Closing the file descriptor removes it from the registry.
+        drop(self.sender.take());
+        self.sender_registered = false;
+    }
+
+    /// Returns `true` if the sending end is closed, see
+    /// [`DuplexPipe::close_write`].
+    pub fn is_write_closed(&self) -> bool {
+        self.sender.is_none()
+    }
+
+    /// Returns references to the receiving and sending end.
+    ///
+    /// The sending end is `None` if it was closed using
+    /// [`DuplexPipe::close_write`]. As both `&Receiver` and `&Sender`
+    /// implement `Read` and `Write` respectively, this can be used to read and
+    /// write concurrently.
+    pub fn split(&self) -> (&Receiver, Option<&Sender>) {
+        (&self.receiver, self.sender.as_ref())
+    }
+
+    /// Convert into the receiving and sending end.
+    ///
+    /// The sending end is `None` if it was closed using
+    /// [`DuplexPipe::close_write`]. Both ends must be deregistered first, if
+    /// they were registered.
+    pub fn into_split(self) -> (Receiver, Option<Sender>) {
+        (self.receiver, self.sender)
+    }
+
+    fn sender(&self) -> io::Result<&Sender> {
+        self.sender.as_ref().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "sending end of duplex pipe is closed",
+            )
+        })
+    }
+
+    /// Update the registration of both ends to `interests`, deregistering
+    /// them if `None`.
+    ///
+    /// The registration state of each end is updated as soon as its
+    /// registration changes, so that it stays correct if updating the other
+    /// end fails.
+    fn update_registration(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Option<Interest>,
+    ) -> io::Result<()> {
+        let readable = interests.is_some_and(|i| i.is_readable());
+        match (self.receiver_registered, readable) {
+            (false, true) => {
+                self.receiver
+                    .register(registry, token, Interest::READABLE)?;
+                self.receiver_registered = true;
+            }
+            (true, true) => self
+                .receiver
+                .reregister(registry, token, Interest::READABLE)?,
+            (true, false) => {
+                self.receiver.deregister(registry)?;
+                self.receiver_registered = false;
+            }
+            (false, false) => {}
+        }
+
+        let writable = interests.is_some_and(|i| i.is_writable());
+        if let Some(sender) = self.sender.as_mut() {
+            match (self.sender_registered, writable) {
+                (false, true) => {
+                    sender.register(registry, token, Interest::WRITABLE)?;
+                    self.sender_registered = true;
+                }
+                (true, true) => sender.reregister(registry, token, Interest::WRITABLE)?,
+                (true, false) => {
+                    sender.deregister(registry)?;
+                    self.sender_registered = false;
+                }
+                (false, false) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl event::Source for DuplexPipe {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.update_registration(registry, token, Some(interests))
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.update_registration(registry, token, Some(interests))
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        // Token is unused when deregistering.
+        self.update_registration(registry, Token(0), None)
+    }
+}
+
+impl Read for DuplexPipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        (&*self).read_vectored(bufs)
+    }
+}
+
+impl Read for &DuplexPipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.receiver).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        (&self.receiver).read_vectored(bufs)
+    }
+}
+
+impl Write for DuplexPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        (&*self).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Write for &DuplexPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sender()?.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        self.sender()?.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sender
+            .as_ref()
+            .map_or(Ok(()), |mut sender| sender.flush())
+    }
+}
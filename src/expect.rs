@@ -0,0 +1,385 @@
+//! Expect-style automation of interactive processes.
+//!
+//! See [`Expect`]. This module requires the `expect` feature, regex patterns
+//! additionally require the `regex` feature.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+use mio::{event, Events, Interest, Poll, Token};
+
+use crate::pty::PtyMaster;
+use crate::{Receiver, Sender};
+
+const READER: Token = Token(0);
+const WRITER: Token = Token(1);
+
+/// Pattern to wait for, see [`Expect::expect`].
+///
+/// Can be created from a `&str`, `String`, `&[u8]` or `Vec<u8>` for literal
+/// patterns, or from a [`regex::bytes::Regex`] if the `regex` feature is
+/// enabled.
+pub enum Pattern {
+    /// Literal bytes.
+    Literal(Vec<u8>),
+    /// Regular expression.
+    #[cfg(feature = "regex")]
+    Regex(regex::bytes::Regex),
+}
+
+impl Pattern {
+    /// Create a regex pattern.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Pattern, regex::Error> {
+        regex::bytes::Regex::new(pattern).map(Pattern::Regex)
+    }
+
+    /// Returns the start and end of the first match in `buf`.
+    fn find(&self, buf: &[u8]) -> Option<(usize, usize)> {
+        match self {
+            Pattern::Literal(literal) if literal.is_empty() => Some((0, 0)),
+            Pattern::Literal(literal) => buf
+                .windows(literal.len())
+                .position(|window| window == &**literal)
+                .map(|start| (start, start + literal.len())),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => regex.find(buf).map(|m| (m.start(), m.end())),
+        }
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Literal(literal) => write!(f, "{:?}", String::from_utf8_lossy(literal)),
+            #[cfg(feature = "regex")]
+            Pattern::Regex(regex) => write!(f, "/{}/", regex.as_str()),
+        }
+    }
+}
+
+impl From<&str> for Pattern {
+    fn from(literal: &str) -> Pattern {
+        Pattern::Literal(literal.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Pattern {
+    fn from(literal: String) -> Pattern {
+        Pattern::Literal(literal.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Pattern {
+    fn from(literal: &[u8]) -> Pattern {
+        Pattern::Literal(literal.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Pattern {
+    fn from(literal: Vec<u8>) -> Pattern {
+        Pattern::Literal(literal)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl From<regex::bytes::Regex> for Pattern {
+    fn from(regex: regex::bytes::Regex) -> Pattern {
+        Pattern::Regex(regex)
+    }
+}
+
+/// Successful match, returned by [`Expect::expect`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Match {
+    /// Output before the match.
+    pub before: Vec<u8>,
+    /// The matched output.
+    pub matched: Vec<u8>,
+}
+
+/// Driver for interactive processes.
+///
+/// `Expect` reads the output of a process using `reader` and writes input
+/// using `writer`, waiting for output is done using a private [`Poll`]
+/// instance. It can be created for the standard in and out of a [`Child`]
+/// using [`Expect::from_child`], or for a pseudo-terminal using
+/// [`Expect::from_pty`].
+///
+/// All output read and input written is recorded in the transcript, see
+/// [`Expect::transcript`]. Note that when using a pseudo-terminal the input
+/// is usually echoed by the terminal, meaning it will show up twice.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::process::{Command, Stdio};
+/// use std::time::Duration;
+///
+/// use mio_pipe::expect::Expect;
+///
+/// # fn main() -> io::Result<()> {
+/// let mut child = Command::new("sh")
+///     .args(["-c", "echo 'name?'; read name; echo \"hello $name\""])
+///     .stdin(Stdio::piped())
+///     .stdout(Stdio::piped())
+///     .spawn()?;
+///
+/// let mut expect = Expect::from_child(&mut child)?;
+/// expect.expect("name?", Duration::from_secs(5))?;
+/// expect.send_line("world", Duration::from_secs(5))?;
+/// expect.expect("hello world", Duration::from_secs(5))?;
+/// expect.expect_eof(Duration::from_secs(5))?;
+/// # child.wait()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Expect<R, W> {
+    poll: Poll,
+    events: Events,
+    reader: R,
+    writer: W,
+    writer_registered: bool,
+    /// Output not yet matched.
+    buf: Vec<u8>,
+    /// Everything read and written.
+    transcript: Vec<u8>,
+    /// Reader returned end of file.
+    eof: bool,
+}
+
+impl<R, W> Expect<R, W>
+where
+    R: Read + event::Source,
+    W: Write + event::Source,
+{
+    /// Create a new `Expect`, reading output from `reader` and writing input
+    /// to `writer`.
+    ///
+    /// Both `reader` and `writer` must be in non-blocking mode.
+    pub fn new(mut reader: R, writer: W) -> io::Result<Expect<R, W>> {
+        let poll = Poll::new()?;
+        poll.registry()
+            .register(&mut reader, READER, Interest::READABLE)?;
+        Ok(Expect {
+            poll,
+            events: Events::with_capacity(8),
+            reader,
+            writer,
+            writer_registered: false,
+            buf: Vec::new(),
+            transcript: Vec::new(),
+            eof: false,
+        })
+    }
+
+    /// Wait for `pattern` to show up in the output, for at most `timeout`.
+    ///
+    /// On success all output up to and including the match is consumed.
+    ///
+    /// Returns an error of kind [`TimedOut`] if the pattern didn't show up
+    /// within `timeout`, or [`UnexpectedEof`] if the output ended.
+    ///
+    /// [`TimedOut`]: io::ErrorKind::TimedOut
+    /// [`UnexpectedEof`]: io::ErrorKind::UnexpectedEof
+    pub fn expect<P: Into<Pattern>>(&mut self, pattern: P, timeout: Duration) -> io::Result<Match> {
+        let pattern = pattern.into();
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some((start, end)) = pattern.find(&self.buf) {
+                let mut before: Vec<u8> = self.buf.drain(..end).collect();
+                let matched = before.split_off(start);
+                return Ok(Match { before, matched });
+            }
+            if self.eof {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("end of output while waiting for {:?}", pattern),
+                ));
+            }
+            if !self.fill(deadline)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("timed out waiting for {:?}", pattern),
+                ));
+            }
+        }
+    }
+
+    /// Wait for the output to end, for at most `timeout`.
+    ///
+    /// Returns the remaining output.
+    pub fn expect_eof(&mut self, timeout: Duration) -> io::Result<Vec<u8>> {
+        let deadline = Instant::now() + timeout;
+        while !self.eof {
+            if !self.fill(deadline)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out waiting for end of output",
+                ));
+            }
+        }
+        Ok(self.buf.split_off(0))
+    }
+
+    /// Read more output, returns `false` if the deadline passed.
+    fn fill(&mut self, deadline: Instant) -> io::Result<bool> {
+        let mut buf = [0; 4096];
+        loop {
+            match self.reader.read(&mut buf) {
+                Ok(0) => {
+                    self.eof = true;
+                    return Ok(true);
+                }
+                Ok(n) => {
+                    self.buf.extend_from_slice(&buf[..n]);
+                    self.transcript.extend_from_slice(&buf[..n]);
+                    return Ok(true);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    if timeout == Duration::from_secs(0) {
+                        return Ok(false);
+                    }
+                    self.poll_events(Some(timeout))?;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Write `input`, waiting for at most `timeout` for all of it to be
+    /// written.
+    ///
+    /// Returns an error of kind [`TimedOut`] if not all input could be
+    /// written within `timeout`. Input that was written is recorded in the
+    /// transcript.
+    ///
+    /// [`TimedOut`]: io::ErrorKind::TimedOut
+    pub fn send(&mut self, input: &[u8], timeout: Duration) -> io::Result<()> {
+        self.send_before(input, Instant::now() + timeout)
+    }
+
+    /// Write `line` followed by a newline, see [`Expect::send`].
+    pub fn send_line(&mut self, line: &str, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        self.send_before(line.as_bytes(), deadline)?;
+        self.send_before(b"\n", deadline)
+    }
+
+    /// Write `input`, waiting until `deadline` for all of it to be written.
+    fn send_before(&mut self, input: &[u8], deadline: Instant) -> io::Result<()> {
+        let mut written = 0;
+        while written < input.len() {
+            match self.writer.write(&input[written..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.transcript
+                        .extend_from_slice(&input[written..written + n]);
+                    written += n;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if !self.writer_registered {
+                        self.poll.registry().register(
+                            &mut self.writer,
+                            WRITER,
+                            Interest::WRITABLE,
+                        )?;
+                        self.writer_registered = true;
+                        // Could have become writable before registering.
+                        continue;
+                    }
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    if timeout == Duration::from_secs(0) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!(
+                                "timed out sending input, {} of {} bytes written",
+                                written,
+                                input.len()
+                            ),
+                        ));
+                    }
+                    self.poll_events(Some(timeout))?;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_events(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match self.poll.poll(&mut self.events, timeout) {
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => Ok(()),
+            res => res,
+        }
+    }
+}
+
+impl<R, W> Expect<R, W> {
+    /// Returns all output read and input written so far.
+    pub fn transcript(&self) -> &[u8] {
+        &self.transcript
+    }
+
+    /// Returns the output read, but not yet matched.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Expect<Receiver, Sender> {
+    /// Create a new `Expect` for the standard in and out of `child`.
+    ///
+    /// Both must be configured using [`Stdio::piped`], this takes them from
+    /// `child`.
+    ///
+    /// [`Stdio::piped`]: std::process::Stdio::piped
+    pub fn from_child(child: &mut Child) -> io::Result<Expect<Receiver, Sender>> {
+        match (child.stdout.take(), child.stdin.take()) {
+            (Some(stdout), Some(stdin)) => {
+                let reader = Receiver::from(stdout);
+                reader.set_nonblocking(true)?;
+                let writer = Sender::from(stdin);
+                writer.set_nonblocking(true)?;
+                Expect::new(reader, writer)
+            }
+            (stdout, stdin) => {
+                // Don't take only one of them.
+                child.stdout = stdout;
+                child.stdin = stdin;
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "standard in and out of child must be piped",
+                ))
+            }
+        }
+    }
+}
+
+impl Expect<PtyMaster, PtyMaster> {
+    /// Create a new `Expect` for the master end of a pseudo-terminal, see
+    /// [`new_pty`].
+    ///
+    /// [`new_pty`]: crate::pty::new_pty
+    pub fn from_pty(master: PtyMaster) -> io::Result<Expect<PtyMaster, PtyMaster>> {
+        let writer = master.try_clone()?;
+        Expect::new(master, writer)
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug> fmt::Debug for Expect<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expect")
+            .field("reader", &self.reader)
+            .field("writer", &self.writer)
+            .field("buffer", &String::from_utf8_lossy(&self.buf))
+            .field("eof", &self.eof)
+            .finish()
+    }
+}
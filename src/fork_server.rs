@@ -0,0 +1,473 @@
+//! Fork server (also known as zygote).
+//!
+//! See [`ForkServer`].
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use mio::{event, Interest, Registry, Token};
+
+use crate::{new_pipe, sys, Receiver, Sender};
+
+/// Size of the request header: request id (`u64`) and size of the arguments
+/// (`u32`).
+const REQUEST_HEADER_SIZE: usize = 12;
+/// Size of a response: request id (`u64`) and pid or negative errno (`i64`).
+const RESPONSE_SIZE: usize = 16;
+
+/// Function run by a worker process.
+///
+/// It's called with the arguments passed to [`ForkServer::request`] and the
+/// worker's ends of the pipes to the parent process. The returned value is used
+/// as exit code of the worker process.
+pub type WorkerMain = fn(args: &[u8], receiver: Receiver, sender: Sender) -> i32;
+
+/// Fork server that spawns worker processes on request.
+///
+/// Calling `fork(2)` in a multi-threaded process is hard to do correctly. The
+/// `ForkServer` solves this by forking a server process early, *before* any
+/// threads are started. Later on the parent process can request the server to
+/// fork a worker process (running [`WorkerMain`]) over a pipe, without having
+/// to fork itself.
+///
+/// Each worker is connected to the parent process by two pipes, see
+/// [`Worker`]. As file descriptors can't be send over a pipe these are created
+/// as named pipes in a private temporary directory, which are removed once the
+/// worker is spawned.
+///
+/// # Events
+///
+/// The `ForkServer` can be registered with [`READABLE`] interest, it will
+/// receive an event once a response to a request (made using
+/// [`ForkServer::request`]) is available, after which
+/// [`ForkServer::try_response`] should be called. For a blocking version see
+/// [`ForkServer::spawn`].
+///
+/// [`READABLE`]: Interest::READABLE
+///
+/// # Notes
+///
+/// Workers are children of the server process, not the parent process, they
+/// are reaped by the server. Use the [`Worker`]'s receiver to detect when the
+/// worker exits.
+///
+/// Once the `ForkServer` is dropped the server process exits, running workers
+/// are not stopped.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Read, Write};
+///
+/// use mio_pipe::fork_server::ForkServer;
+/// use mio_pipe::{Receiver, Sender};
+///
+/// fn worker(args: &[u8], _: Receiver, mut sender: Sender) -> i32 {
+///     sender.set_nonblocking(false).unwrap();
+///     sender.write_all(args).unwrap();
+///     0
+/// }
+///
+/// # fn main() -> io::Result<()> {
+/// // Start the fork server before starting any threads.
+/// let mut fork_server = unsafe { ForkServer::start(worker)? };
+///
+/// let worker = fork_server.spawn(b"Hello world")?;
+/// let (_, mut receiver) = worker.into_parts();
+/// receiver.set_nonblocking(false)?;
+/// let mut output = Vec::new();
+/// receiver.read_to_end(&mut output)?;
+/// assert_eq!(output, b"Hello world");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ForkServer {
+    /// Blocking write end used to send requests, `None` after dropping.
+    requests: Option<Sender>,
+    /// Non-blocking read end used to receive responses.
+    responses: Receiver,
+    /// Process id of the server.
+    pid: libc::pid_t,
+    /// Temporary directory holding the named pipes.
+    dir: PathBuf,
+    /// Id of the next request.
+    next_id: u64,
+    /// The parent's ends of the pipes of pending requests.
+    pending: HashMap<u64, (Sender, Receiver)>,
+    /// Partially read response.
+    buf: Vec<u8>,
+    /// Responses read by `spawn` for other requests.
+    completed: VecDeque<(RequestId, io::Result<Worker>)>,
+}
+
+impl ForkServer {
+    /// Start the fork server.
+    ///
+    /// # Safety
+    ///
+    /// This must be called while the process is single threaded, i.e. before
+    /// any threads are started.
+    pub unsafe fn start(worker: WorkerMain) -> io::Result<ForkServer> {
+        let dir = create_dir()?;
+        let (requests_sender, requests_receiver) = new_pipe()?;
+        let (responses_sender, responses_receiver) = new_pipe()?;
+        requests_sender.set_nonblocking(false)?;
+        requests_receiver.set_nonblocking(false)?;
+        responses_sender.set_nonblocking(false)?;
+        // Don't let the workers write output buffered before the fork (again).
+        io::stdout().flush()?;
+
+        match libc::fork() {
+            -1 => {
+                let err = io::Error::last_os_error();
+                let _ = fs::remove_dir(&dir);
+                Err(err)
+            }
+            0 => {
+                drop(requests_sender);
+                drop(responses_receiver);
+                run_server(&dir, worker, requests_receiver, responses_sender)
+            }
+            pid => Ok(ForkServer {
+                requests: Some(requests_sender),
+                responses: responses_receiver,
+                pid,
+                dir,
+                next_id: 0,
+                pending: HashMap::new(),
+                buf: Vec::with_capacity(RESPONSE_SIZE),
+                completed: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Request the server to spawn a new worker, passing it `args`.
+    ///
+    /// The response can be retrieved using [`ForkServer::try_response`].
+    pub fn request(&mut self, args: &[u8]) -> io::Result<RequestId> {
+        if args.len() > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "arguments too large",
+            ));
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let (input, output) = fifo_paths(&self.dir, id);
+        make_fifo(&input)?;
+        if let Err(err) = make_fifo(&output) {
+            let _ = fs::remove_file(&input);
+            return Err(err);
+        }
+        // NOTE: we open the input with read and write access, so the open
+        // doesn't fail if the worker isn't started yet.
+        let ends = open_fifo(&input, true, true)
+            .and_then(|sender| open_fifo(&output, true, false).map(|receiver| (sender, receiver)));
+        let ends = match ends {
+            Ok(ends) => ends,
+            Err(err) => {
+                let _ = fs::remove_file(&input);
+                let _ = fs::remove_file(&output);
+                return Err(err);
+            }
+        };
+        let _ = self.pending.insert(id, ends);
+
+        let mut request = Vec::with_capacity(REQUEST_HEADER_SIZE + args.len());
+        request.extend_from_slice(&id.to_ne_bytes());
+        request.extend_from_slice(&(args.len() as u32).to_ne_bytes());
+        request.extend_from_slice(args);
+        let requests = self.requests.as_mut().unwrap();
+        if let Err(err) = requests.write_all(&request) {
+            self.remove_pending(id);
+            return Err(err);
+        }
+        Ok(RequestId(id))
+    }
+
+    /// Attempt to retrieve a response to a request made using
+    /// [`ForkServer::request`], without blocking.
+    ///
+    /// Returns `Ok(None)` if no response is available. The outer error is
+    /// returned if the communication with the server fails, the inner error
+    /// if spawning the worker failed.
+    pub fn try_response(&mut self) -> io::Result<Option<(RequestId, io::Result<Worker>)>> {
+        if let Some(response) = self.completed.pop_front() {
+            return Ok(Some(response));
+        }
+
+        let mut buf = [0; RESPONSE_SIZE];
+        loop {
+            let want = RESPONSE_SIZE - self.buf.len();
+            match self.responses.read(&mut buf[..want]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "fork server exited",
+                    ))
+                }
+                Ok(n) => self.buf.extend_from_slice(&buf[..n]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+
+            if self.buf.len() == RESPONSE_SIZE {
+                let mut id = [0; 8];
+                let mut result = [0; 8];
+                id.copy_from_slice(&self.buf[..8]);
+                result.copy_from_slice(&self.buf[8..]);
+                self.buf.clear();
+                let id = u64::from_ne_bytes(id);
+                let result = i64::from_ne_bytes(result);
+                return Ok(Some((RequestId(id), self.complete(id, result))));
+            }
+        }
+    }
+
+    /// Spawn a new worker, passing it `args`, blocking until the worker is
+    /// spawned.
+    pub fn spawn(&mut self, args: &[u8]) -> io::Result<Worker> {
+        let request_id = self.request(args)?;
+        let mut other = Vec::new();
+        let result = loop {
+            match self.try_response()? {
+                Some((id, result)) if id == request_id => break result,
+                Some(response) => other.push(response),
+                None => {
+                    let mut fds = [libc::pollfd {
+                        fd: self.responses.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    }];
+                    let _ = sys::poll(&mut fds, None)?;
+                }
+            }
+        };
+        self.completed.extend(other);
+        result
+    }
+
+    /// Complete the request with `id`, `result` is either the pid or a
+    /// negative errno.
+    fn complete(&mut self, id: u64, result: i64) -> io::Result<Worker> {
+        let (input, output) = fifo_paths(&self.dir, id);
+        let _ = fs::remove_file(&input);
+        let _ = fs::remove_file(&output);
+        let (sender, receiver) = self.pending.remove(&id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "unknown fork server response")
+        })?;
+        if result < 0 {
+            Err(io::Error::from_raw_os_error(-result as i32))
+        } else {
+            Ok(Worker {
+                pid: result as u32,
+                sender,
+                receiver,
+            })
+        }
+    }
+
+    fn remove_pending(&mut self, id: u64) {
+        let (input, output) = fifo_paths(&self.dir, id);
+        let _ = fs::remove_file(&input);
+        let _ = fs::remove_file(&output);
+        let _ = self.pending.remove(&id);
+    }
+}
+
+impl event::Source for ForkServer {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.responses.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.responses.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.responses.deregister(registry)
+    }
+}
+
+impl Drop for ForkServer {
+    fn drop(&mut self) {
+        // Closing the requests pipe stops the server.
+        drop(self.requests.take());
+        let mut status = 0;
+        let _ = unsafe { libc::waitpid(self.pid, &mut status, 0) };
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Id of a request made by [`ForkServer::request`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RequestId(u64);
+
+/// Worker process spawned by the [`ForkServer`].
+#[derive(Debug)]
+pub struct Worker {
+    pid: u32,
+    /// Sends data to the worker's receiver.
+    sender: Sender,
+    /// Receives data from the worker's sender.
+    receiver: Receiver,
+}
+
+impl Worker {
+    /// Returns the process id of the worker.
+    pub fn id(&self) -> u32 {
+        self.pid
+    }
+
+    /// Returns the sending end of the pipe to the worker.
+    ///
+    /// Note that the sending end is also opened for reading, which means that
+    /// writes don't fail once the worker exited. Use the receiving end to
+    /// detect that.
+    pub fn sender(&mut self) -> &mut Sender {
+        &mut self.sender
+    }
+
+    /// Returns the receiving end of the pipe from the worker.
+    pub fn receiver(&mut self) -> &mut Receiver {
+        &mut self.receiver
+    }
+
+    /// Returns the sending and receiving ends of the pipes.
+    pub fn into_parts(self) -> (Sender, Receiver) {
+        (self.sender, self.receiver)
+    }
+}
+
+/// Run the server process, never returns.
+fn run_server(dir: &Path, worker: WorkerMain, mut requests: Receiver, mut responses: Sender) -> ! {
+    // Let the OS reap the worker processes.
+    unsafe {
+        let _ = libc::signal(libc::SIGCHLD, libc::SIG_IGN);
+    }
+
+    loop {
+        let mut header = [0; REQUEST_HEADER_SIZE];
+        if requests.read_exact(&mut header).is_err() {
+            // Parent process dropped the `ForkServer` (or exited).
+            unsafe { libc::_exit(0) }
+        }
+        let mut id = [0; 8];
+        let mut len = [0; 4];
+        id.copy_from_slice(&header[..8]);
+        len.copy_from_slice(&header[8..]);
+        let id = u64::from_ne_bytes(id);
+        let mut args = vec![0; u32::from_ne_bytes(len) as usize];
+        if requests.read_exact(&mut args).is_err() {
+            unsafe { libc::_exit(1) }
+        }
+
+        let result = match spawn_worker(dir, id, &args, worker, &requests, &responses) {
+            Ok(pid) => i64::from(pid),
+            Err(err) => -i64::from(err.raw_os_error().unwrap_or(libc::EINVAL)),
+        };
+        let mut response = [0; RESPONSE_SIZE];
+        response[..8].copy_from_slice(&id.to_ne_bytes());
+        response[8..].copy_from_slice(&result.to_ne_bytes());
+        if responses.write_all(&response).is_err() {
+            unsafe { libc::_exit(1) }
+        }
+    }
+}
+
+/// Spawn a worker process for request `id`, returning its pid.
+fn spawn_worker(
+    dir: &Path,
+    id: u64,
+    args: &[u8],
+    worker: WorkerMain,
+    requests: &Receiver,
+    responses: &Sender,
+) -> io::Result<libc::pid_t> {
+    let (input, output) = fifo_paths(dir, id);
+    // The parent process opened both named pipes already, so these don't
+    // block.
+    let receiver: Receiver = open_fifo(&input, true, false)?;
+    let sender: Sender = open_fifo(&output, false, true)?;
+
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => {
+            // Worker process, close the server's pipes.
+            unsafe {
+                let _ = libc::close(requests.as_raw_fd());
+                let _ = libc::close(responses.as_raw_fd());
+                let _ = libc::signal(libc::SIGCHLD, libc::SIG_DFL);
+            }
+            let code = worker(args, receiver, sender);
+            // Don't run the parent's `atexit` handlers, only flush the output
+            // of the worker.
+            let _ = io::stdout().flush();
+            unsafe { libc::_exit(code) }
+        }
+        pid => Ok(pid),
+    }
+}
+
+/// Create a private temporary directory.
+fn create_dir() -> io::Result<PathBuf> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "mio-pipe-fork-server-{}-{}",
+        process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let dir = std::env::temp_dir().join(name);
+    DirBuilder::new().mode(0o700).create(&dir)?;
+    Ok(dir)
+}
+
+/// Returns the paths to the named pipes for the worker's input and output.
+fn fifo_paths(dir: &Path, id: u64) -> (PathBuf, PathBuf) {
+    (
+        dir.join(format!("{}.in", id)),
+        dir.join(format!("{}.out", id)),
+    )
+}
+
+fn make_fifo(path: &Path) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Open the named pipe at `path`, non-blocking.
+fn open_fifo<T: FromRawFd>(path: &Path, read: bool, write: bool) -> io::Result<T> {
+    OpenOptions::new()
+        .read(read)
+        .write(write)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map(|file| unsafe { T::from_raw_fd(file.into_raw_fd()) })
+}
@@ -0,0 +1,465 @@
+//! GNU make jobserver.
+//!
+//! This module implements the [GNU make jobserver protocol], both the
+//! [`Client`] side, used when running inside of `make` (or a similar tool), and
+//! the [`Server`] side, used to limit the parallelism of child processes.
+//!
+//! [GNU make jobserver protocol]: https://www.gnu.org/software/make/manual/html_node/Job-Slots.html
+//!
+//! # Notes
+//!
+//! Per the protocol every process gets a single implicit token, which isn't
+//! managed by this module. In other words a process can always run one job
+//! without acquiring a token.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use mio::{event, Interest, Registry, Token};
+
+use crate::process::clear_cloexec;
+use crate::{new_pipe, Receiver, Sender};
+
+/// The byte used as token, same as used by GNU make.
+const TOKEN: u8 = b'+';
+
+/// Environment variables checked by [`Client::from_env`], in order.
+const ENV_VARS: &[&str] = &["CARGO_MAKEFLAGS", "MAKEFLAGS", "MFLAGS"];
+
+/// Client side of the jobserver.
+///
+/// The `Client` can be registered with [`READABLE`] interest, an event is
+/// generated once a token (might) be available, after which [`try_acquire`]
+/// should be called.
+///
+/// [`READABLE`]: Interest::READABLE
+/// [`try_acquire`]: Client::try_acquire
+#[derive(Debug)]
+pub struct Client {
+    /// Non-blocking read end, used to acquire tokens.
+    receiver: Receiver,
+    /// Non-blocking write end, used to release tokens.
+    sender: Arc<Sender>,
+    /// The original jobserver, passed to child processes.
+    auth: Auth,
+}
+
+impl Client {
+    /// Create a `Client` based on the jobserver passed in the environment.
+    ///
+    /// This checks the `CARGO_MAKEFLAGS`, `MAKEFLAGS` and `MFLAGS`
+    /// environment variables (in that order) for the `--jobserver-auth` (or
+    /// older `--jobserver-fds`) argument. Both file descriptors (`R,W`) and
+    /// named pipes (`fifo:PATH`) are supported.
+    ///
+    /// Returns `Ok(None)` if no jobserver is found in the environment.
+    ///
+    /// # Notes
+    ///
+    /// The file descriptors are shared with the parent process (and possibly
+    /// others), so setting them to non-blocking would affect those processes
+    /// as well. On Linux this is avoided by reopening the pipe via `/proc`, on
+    /// other platforms the file descriptors are duplicated and set to
+    /// non-blocking.
+    ///
+    /// # Safety
+    ///
+    /// If the jobserver uses file descriptors, the `Client` takes ownership of
+    /// them. The caller must ensure the file descriptors are not owned by
+    /// anything else, i.e. this function should only be called once.
+    pub unsafe fn from_env() -> io::Result<Option<Client>> {
+        for var in ENV_VARS {
+            if let Some(flags) = env::var_os(var) {
+                if let Some(auth) = parse_makeflags(flags.as_bytes())? {
+                    return Client::new(auth).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn new(auth: Auth) -> io::Result<Client> {
+        let (sender, receiver) = match &auth {
+            Auth::Pipe { read, write } => {
+                let receiver = reopen(read, false)?;
+                let sender = reopen(write, true)?;
+                (sender, receiver)
+            }
+            Auth::Fifo { path, .. } => {
+                // Opening the named pipe gives us our own file description, so
+                // we can safely set them to non-blocking.
+                let receiver = open_fifo(path, false)?;
+                let sender = open_fifo(path, true)?;
+                (sender, receiver)
+            }
+        };
+        Ok(Client {
+            receiver,
+            sender: Arc::new(sender),
+            auth,
+        })
+    }
+
+    /// Attempt to acquire a token, without blocking.
+    ///
+    /// Returns `Ok(None)` if no token is available, once one (might) be
+    /// available an event is generated for the `Client`.
+    ///
+    /// The token is released once the returned [`Acquired`] is dropped.
+    pub fn try_acquire(&self) -> io::Result<Option<Acquired>> {
+        let mut buf = [0; 1];
+        loop {
+            match (&self.receiver).read(&mut buf) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "jobserver closed",
+                    ))
+                }
+                Ok(_) => {
+                    return Ok(Some(Acquired {
+                        sender: self.sender.clone(),
+                        byte: buf[0],
+                    }))
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Configure `cmd` to use this jobserver.
+    ///
+    /// This sets the `MAKEFLAGS` environment variable and ensures the file
+    /// descriptors (if any) are inherited by the child process.
+    pub fn configure(&self, cmd: &mut Command) {
+        self.auth.configure(cmd)
+    }
+}
+
+impl event::Source for Client {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.receiver.deregister(registry)
+    }
+}
+
+/// Token acquired from a jobserver, see [`Client::try_acquire`].
+///
+/// The token is released when this is dropped.
+#[derive(Debug)]
+pub struct Acquired {
+    sender: Arc<Sender>,
+    byte: u8,
+}
+
+impl Drop for Acquired {
+    fn drop(&mut self) {
+        // Per the protocol we must return the same byte we read. Since the
+        // number of tokens is limited the pipe can't be full, the only errors
+        // we can get are ones we can't handle.
+        let _ = (&*self.sender).write(&[self.byte]);
+    }
+}
+
+/// Server side of the jobserver.
+///
+/// Use [`Server::configure`] to pass the jobserver to child processes, e.g.
+/// `make` or `cargo`.
+#[derive(Debug)]
+pub struct Server {
+    auth: Auth,
+}
+
+impl Server {
+    /// Create a new jobserver, using an anonymous pipe, with `tokens` tokens.
+    ///
+    /// Note that per the protocol every process already has a single implicit
+    /// token, so to allow `N` jobs in parallel this should be `N - 1`.
+    pub fn new(tokens: usize) -> io::Result<Server> {
+        let (sender, receiver) = new_pipe()?;
+        add_tokens(&sender, tokens)?;
+        // The file descriptors are shared with the child processes, which
+        // expect blocking I/O.
+        sender.set_nonblocking(false)?;
+        receiver.set_nonblocking(false)?;
+        let auth = unsafe {
+            Auth::Pipe {
+                read: File::from_raw_fd(receiver.into_raw_fd()),
+                write: File::from_raw_fd(sender.into_raw_fd()),
+            }
+        };
+        Ok(Server { auth })
+    }
+
+    /// Create a new jobserver, using a named pipe at `path`, with `tokens`
+    /// tokens.
+    ///
+    /// The named pipe is removed when the `Server` is dropped. Note that
+    /// support for named pipes was added in GNU make 4.4.
+    ///
+    /// Also see the note about the implicit token in [`Server::new`].
+    pub fn new_fifo<P: AsRef<Path>>(path: P, tokens: usize) -> io::Result<Server> {
+        let path = path.as_ref().to_path_buf();
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Open both ends so that opening the named pipe never blocks and the
+        // tokens are kept alive.
+        let file = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+        {
+            Ok(file) => file,
+            Err(err) => {
+                let _ = fs::remove_file(&path);
+                return Err(err);
+            }
+        };
+        if let Err(err) = add_tokens(&file, tokens) {
+            let _ = fs::remove_file(&path);
+            return Err(err);
+        }
+        Ok(Server {
+            auth: Auth::Fifo {
+                path,
+                _file: Some(file),
+            },
+        })
+    }
+
+    /// Create a [`Client`] for this jobserver.
+    pub fn client(&self) -> io::Result<Client> {
+        Client::new(self.auth.try_clone()?)
+    }
+
+    /// Configure `cmd` to use this jobserver.
+    ///
+    /// This sets the `MAKEFLAGS` environment variable and ensures the file
+    /// descriptors (if any) are inherited by the child process.
+    pub fn configure(&self, cmd: &mut Command) {
+        self.auth.configure(cmd)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Auth::Fifo { path, .. } = &self.auth {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// How to connect to the jobserver.
+#[derive(Debug)]
+enum Auth {
+    /// Anonymous pipe, passed as file descriptors.
+    Pipe { read: File, write: File },
+    /// Named pipe.
+    Fifo {
+        path: PathBuf,
+        /// Used by the `Server` to keep the named pipe alive.
+        _file: Option<File>,
+    },
+}
+
+impl Auth {
+    fn try_clone(&self) -> io::Result<Auth> {
+        match self {
+            Auth::Pipe { read, write } => Ok(Auth::Pipe {
+                read: read.try_clone()?,
+                write: write.try_clone()?,
+            }),
+            Auth::Fifo { path, .. } => Ok(Auth::Fifo {
+                path: path.clone(),
+                _file: None,
+            }),
+        }
+    }
+
+    fn configure(&self, cmd: &mut Command) {
+        match self {
+            Auth::Pipe { read, write } => {
+                let read = read.as_raw_fd();
+                let write = write.as_raw_fd();
+                let mut flags = OsString::from("-j --jobserver-fds=");
+                flags.push(format!(
+                    "{},{} --jobserver-auth={},{}",
+                    read, write, read, write
+                ));
+                let _ = cmd.env("MAKEFLAGS", flags);
+                unsafe {
+                    // The file descriptors have `FD_CLOEXEC` set, clear it in
+                    // the child process so they're inherited.
+                    let _ = cmd.pre_exec(move || {
+                        clear_cloexec(read)?;
+                        clear_cloexec(write)
+                    });
+                }
+            }
+            Auth::Fifo { path, .. } => {
+                let mut flags = OsString::from("-j --jobserver-auth=fifo:");
+                flags.push(path);
+                let _ = cmd.env("MAKEFLAGS", flags);
+            }
+        }
+    }
+}
+
+/// Parse the jobserver from `MAKEFLAGS`, using the last `--jobserver-auth` or
+/// `--jobserver-fds` argument (as later arguments override earlier ones).
+///
+/// If the jobserver uses file descriptors this takes ownership of them.
+unsafe fn parse_makeflags(flags: &[u8]) -> io::Result<Option<Auth>> {
+    let value = flags
+        .rsplit(|b| *b == b' ')
+        .filter_map(|arg| {
+            arg.strip_prefix(b"--jobserver-auth=")
+                .or_else(|| arg.strip_prefix(b"--jobserver-fds="))
+        })
+        .next();
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    if let Some(path) = value.strip_prefix(b"fifo:") {
+        let path = PathBuf::from(std::ffi::OsStr::from_bytes(path));
+        return Ok(Some(Auth::Fifo { path, _file: None }));
+    }
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid jobserver in MAKEFLAGS");
+    let mut fds = value.splitn(2, |b| *b == b',').map(|fd| {
+        std::str::from_utf8(fd)
+            .ok()
+            .and_then(|fd| fd.parse::<RawFd>().ok())
+            .filter(|fd| *fd >= 0)
+    });
+    let (read, write) = match (fds.next(), fds.next()) {
+        (Some(Some(read)), Some(Some(write))) => (read, write),
+        _ => return Err(invalid()),
+    };
+
+    for fd in &[read, write] {
+        // GNU make doesn't pass the file descriptors to commands that are not
+        // marked as recursive (`+`), but still sets `MAKEFLAGS`.
+        if libc::fcntl(*fd, libc::F_GETFD) == -1 {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "jobserver file descriptor in MAKEFLAGS not open",
+            ));
+        }
+    }
+    for fd in &[read, write] {
+        // Don't leak the file descriptors into child processes, see
+        // `Auth::configure` to pass them explicitly.
+        if libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(Some(Auth::Pipe {
+        read: File::from_raw_fd(read),
+        write: File::from_raw_fd(write),
+    }))
+}
+
+/// Returns a non-blocking version of `file`, without changing the flags of
+/// `file` itself.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn reopen<T: FromRawFd>(file: &File, write: bool) -> io::Result<T> {
+    let path = format!("/proc/self/fd/{}", file.as_raw_fd());
+    match OpenOptions::new()
+        .read(!write)
+        .write(write)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+    {
+        Ok(file) => Ok(unsafe { T::from_raw_fd(file.into_raw_fd()) }),
+        // `/proc` might not be mounted.
+        Err(_) => dup_nonblocking(file),
+    }
+}
+
+/// Returns a non-blocking version of `file`.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn reopen<T: FromRawFd>(file: &File, _: bool) -> io::Result<T> {
+    dup_nonblocking(file)
+}
+
+/// Duplicate `file` and set it to non-blocking, note that this also sets
+/// `file` to non-blocking.
+fn dup_nonblocking<T: FromRawFd>(file: &File) -> io::Result<T> {
+    let file = file.try_clone()?;
+    let value = 1 as libc::c_int;
+    if unsafe { libc::ioctl(file.as_raw_fd(), libc::FIONBIO, &value) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { T::from_raw_fd(file.into_raw_fd()) })
+}
+
+fn open_fifo<T: FromRawFd>(path: &Path, write: bool) -> io::Result<T> {
+    OpenOptions::new()
+        .read(!write)
+        .write(write)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+        .map(|file| unsafe { T::from_raw_fd(file.into_raw_fd()) })
+}
+
+/// Write `tokens` tokens into the jobserver.
+fn add_tokens<W>(sender: W, tokens: usize) -> io::Result<()>
+where
+    W: Write,
+{
+    let mut sender = sender;
+    let buf = [TOKEN; 256];
+    let mut left = tokens;
+    while left > 0 {
+        let n = left.min(buf.len());
+        match sender.write(&buf[..n]) {
+            Ok(n) => left -= n,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "too many jobserver tokens",
+                ))
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
@@ -19,17 +19,68 @@
 //! a port to Windows please see [issue #6].
 //!
 //! [issue #6]: https://github.com/Thomasdezeeuw/mio-pipe/issues/6
+//!
+//! ## Features
+//!
+//! * `expect`: enables the `expect` module.
+//! * `regex`: enables regex patterns in the `expect` module.
+//! * `std-pipe`: enables conversions from and to [`std::io::PipeReader`] and
+//!   [`std::io::PipeWriter`], requires Rust 1.87.
+//! * `os_pipe`: enables conversions from and to the types of the [`os_pipe`]
+//!   crate.
+//!
+//! [`std::io::PipeReader`]: https://doc.rust-lang.org/std/io/struct.PipeReader.html
+//! [`std::io::PipeWriter`]: https://doc.rust-lang.org/std/io/struct.PipeWriter.html
+//! [`os_pipe`]: https://docs.rs/os_pipe
 
+#[cfg(all(unix, any(feature = "std-pipe", feature = "os_pipe")))]
+use std::convert::TryFrom;
+#[cfg(unix)]
+use std::env;
+#[cfg(unix)]
+use std::ffi::OsStr;
 use std::io::{self, IoSlice, IoSliceMut, Read, Write};
 #[cfg(unix)]
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+#[cfg(unix)]
+use std::process::{ChildStderr, ChildStdin, ChildStdout, Stdio};
 #[cfg(unix)]
-use std::process::{ChildStderr, ChildStdin, ChildStdout};
+use std::time::Duration;
 
 use mio::{event, Interest, Registry, Token};
 
+mod registered;
 mod sys;
 
+#[cfg(unix)]
+pub mod cancel;
+pub mod channel;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(unix)]
+pub mod duplex;
+#[cfg(all(unix, feature = "expect"))]
+pub mod expect;
+#[cfg(unix)]
+pub mod fork_server;
+#[cfg(unix)]
+pub mod jobserver;
+#[cfg(unix)]
+pub mod process;
+#[cfg(unix)]
+pub mod pty;
+#[cfg(unix)]
+pub mod readiness;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub mod seqpacket;
+#[cfg(unix)]
+pub mod stdio;
+pub mod waker;
+
+pub use registered::Registered;
+#[cfg(unix)]
+pub use sys::{AccessMode, Flags};
+
 /// Sending end of an Unix pipe.
 ///
 /// See [`new_pipe`] for documentation, including examples.
@@ -43,6 +94,185 @@ impl Sender {
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.inner.set_nonblocking(nonblocking)
     }
+
+    /// Emulate non-blocking mode, without setting `O_NONBLOCK`.
+    ///
+    /// [`Sender::set_nonblocking`] changes the open file description, which is
+    /// shared with all other processes that have a copy of the pipe end, for
+    /// example a child process that inherited it. In emulated mode the file
+    /// descriptor flags are left untouched, instead `poll(2)` (with a zero
+    /// timeout) is used before each write to check if it would block, in which
+    /// case an error of kind [`WouldBlock`] is returned. Writes are limited to
+    /// `PIPE_BUF` bytes per call, as only that many bytes are guaranteed to
+    /// fit in the pipe once it's writable.
+    ///
+    /// # Notes
+    ///
+    /// This is only useful if the pipe is in blocking mode. If other processes
+    /// also write to the pipe, it's possible the write still blocks when
+    /// another process writes in between the check and the write.
+    ///
+    /// [`WouldBlock`]: io::ErrorKind::WouldBlock
+    #[cfg(unix)]
+    pub fn set_nonblocking_emulated(&self, emulate: bool) {
+        self.inner.set_nonblocking_emulated(emulate)
+    }
+
+    /// Returns `true` if non-blocking mode is emulated, see
+    /// [`Sender::set_nonblocking_emulated`].
+    #[cfg(unix)]
+    pub fn is_nonblocking_emulated(&self) -> bool {
+        self.inner.is_nonblocking_emulated()
+    }
+
+    /// Set the policy for `EINTR` errors, see [`InterruptPolicy`].
+    ///
+    /// This applies to the `write` and `write_vectored` methods. Defaults to
+    /// [`InterruptPolicy::Surface`].
+    pub fn set_interrupt_policy(&self, policy: InterruptPolicy) {
+        self.inner.set_max_interrupt_retries(policy.max_retries())
+    }
+
+    /// Returns the policy for `EINTR` errors.
+    ///
+    /// This returns the normalised policy, see [`InterruptPolicy::RetryUpTo`].
+    pub fn interrupt_policy(&self) -> InterruptPolicy {
+        InterruptPolicy::from_max_retries(self.inner.max_interrupt_retries())
+    }
+
+    /// Returns `true` if the `Sender` is in non-blocking mode.
+    #[cfg(unix)]
+    pub fn is_nonblocking(&self) -> io::Result<bool> {
+        self.flags().map(|flags| flags.is_nonblocking())
+    }
+
+    /// Set or clear `FD_CLOEXEC`, which closes the file descriptor when
+    /// executing another program.
+    ///
+    /// Pipes created by [`new_pipe`] have `FD_CLOEXEC` set, to pass a pipe end
+    /// to a child process see [`Sender::into_inheritable`].
+    #[cfg(unix)]
+    pub fn set_cloexec(&self, cloexec: bool) -> io::Result<()> {
+        self.inner.set_cloexec(cloexec)
+    }
+
+    /// Returns `true` if `FD_CLOEXEC` is set.
+    #[cfg(unix)]
+    pub fn is_cloexec(&self) -> io::Result<bool> {
+        self.flags().map(|flags| flags.is_cloexec())
+    }
+
+    /// Returns the file descriptor flags.
+    #[cfg(unix)]
+    pub fn flags(&self) -> io::Result<Flags> {
+        self.inner.flags()
+    }
+
+    /// Wait until the `Sender` is writable, or the `timeout` elapsed.
+    ///
+    /// Returns `false` if the timeout elapsed. This also returns `true` if the
+    /// `Receiver` was dropped, in which case writing returns an error.
+    #[cfg(unix)]
+    pub fn wait_writable(&self, timeout: Duration) -> io::Result<bool> {
+        self.inner.wait_writable(Some(timeout))
+    }
+
+    /// Write `buf`, waiting at most `timeout` for the pipe to become
+    /// writable.
+    ///
+    /// Works in both blocking and non-blocking mode. In blocking mode at most
+    /// `PIPE_BUF` bytes are written, as more could block even if the pipe is
+    /// writable. Returns an error of kind [`TimedOut`] if the timeout elapsed.
+    ///
+    /// [`TimedOut`]: io::ErrorKind::TimedOut
+    #[cfg(unix)]
+    pub fn write_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<usize> {
+        self.inner.write_timeout(buf, timeout)
+    }
+
+    /// Close the `Sender`, returning any error returned by `close(2)`.
+    ///
+    /// Dropping the `Sender` also closes it, but ignores any errors. Note that
+    /// the file descriptor is closed even if an error is returned, e.g. an
+    /// `EINTR` error should not be retried.
+    #[cfg(unix)]
+    pub fn close(self) -> io::Result<()> {
+        self.inner.close()
+    }
+
+    /// Create a `Sender` from `fd`, checking that it's the write end of a pipe.
+    ///
+    /// Unlike the `From` and [`FromRawFd`] implementations this sets the pipe
+    /// to non-blocking mode and sets `FD_CLOEXEC`. Returns an error of kind
+    /// [`InvalidInput`] if `fd` is not a pipe (e.g. a socket or regular file)
+    /// or not opened for writing, in which case `fd` is closed.
+    ///
+    /// This accepts `ChildStdin` directly. `TryFrom` can't be implemented for
+    /// those types, as it would conflict with the existing `From`
+    /// implementations.
+    ///
+    /// [`InvalidInput`]: io::ErrorKind::InvalidInput
+    #[cfg(unix)]
+    pub fn try_from_fd<F: Into<OwnedFd>>(fd: F) -> io::Result<Sender> {
+        sys::Sender::try_from_fd(fd.into()).map(|inner| Sender { inner })
+    }
+
+    /// Convert the `Sender` into a pipe end that can be passed to a child
+    /// process, see [`CommandExt::pass_fd`].
+    ///
+    /// This sets the pipe end into blocking mode.
+    ///
+    /// [`CommandExt::pass_fd`]: process::CommandExt::pass_fd
+    #[cfg(unix)]
+    pub fn into_inheritable(self) -> io::Result<process::Inheritable> {
+        process::Inheritable::new(self)
+    }
+
+    /// Create a `Sender` from the file descriptor in the environment variable
+    /// `key`, e.g. passed by the parent process using [`CommandExt::pass_fd`].
+    ///
+    /// This checks that the file descriptor is the write end of a pipe and sets
+    /// it to non-blocking with `FD_CLOEXEC` set.
+    ///
+    /// [`CommandExt::pass_fd`]: process::CommandExt::pass_fd
+    ///
+    /// # Safety
+    ///
+    /// The returned `Sender` takes ownership of the file descriptor. The caller
+    /// must ensure the file descriptor is not owned by anything else, i.e. it
+    /// was passed by the parent process and this is called only once for it.
+    /// Consider using [`Sender::take_from_env`], which also removes the
+    /// environment variable.
+    #[cfg(unix)]
+    pub unsafe fn from_env<K: AsRef<OsStr>>(key: K) -> io::Result<Sender> {
+        fd_from_env(key.as_ref())
+            .and_then(sys::Sender::from_inherited_fd)
+            .map(|inner| Sender { inner })
+    }
+
+    /// Same as [`Sender::from_env`], but removes the environment variable
+    /// once the `Sender` is created.
+    ///
+    /// # Safety
+    ///
+    /// See [`Sender::from_env`].
+    #[cfg(unix)]
+    pub unsafe fn take_from_env<K: AsRef<OsStr>>(key: K) -> io::Result<Sender> {
+        let key = key.as_ref();
+        let sender = Sender::from_env(key)?;
+        env::remove_var(key);
+        Ok(sender)
+    }
+
+    /// Duplicate the file descriptor.
+    pub(crate) fn try_clone(&self) -> io::Result<Sender> {
+        self.inner.try_clone().map(|inner| Sender { inner })
+    }
+
+    /// Shrink the pipe's buffer to the smallest size supported, where possible.
+    pub(crate) fn shrink_buffer(&self) {
+        self.inner.shrink_buffer()
+    }
 }
 
 impl event::Source for Sender {
@@ -83,6 +313,20 @@ impl Write for Sender {
     }
 }
 
+impl Write for &Sender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.inner).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        (&self.inner).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.inner).flush()
+    }
+}
+
 /// # Notes
 ///
 /// The underlying pipe is **not** set to non-blocking.
@@ -117,6 +361,45 @@ impl IntoRawFd for Sender {
     }
 }
 
+#[cfg(unix)]
+impl AsFd for Sender {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// # Notes
+///
+/// The file descriptor is **not** set to non-blocking, nor is it checked to be
+/// a pipe.
+#[cfg(unix)]
+impl From<OwnedFd> for Sender {
+    fn from(fd: OwnedFd) -> Sender {
+        Sender {
+            inner: sys::Sender::from(fd),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl From<Sender> for OwnedFd {
+    fn from(sender: Sender) -> OwnedFd {
+        sender.inner.into()
+    }
+}
+
+/// # Notes
+///
+/// The pipe is **not** set to blocking mode, which most programs expect for
+/// their standard streams, use [`Sender::set_nonblocking`] to change it before
+/// converting.
+#[cfg(unix)]
+impl From<Sender> for Stdio {
+    fn from(sender: Sender) -> Stdio {
+        Stdio::from(OwnedFd::from(sender))
+    }
+}
+
 /// Receiving end of an Unix pipe.
 ///
 /// See [`new_pipe`] for documentation, including examples.
@@ -130,6 +413,177 @@ impl Receiver {
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.inner.set_nonblocking(nonblocking)
     }
+
+    /// Emulate non-blocking mode, without setting `O_NONBLOCK`.
+    ///
+    /// [`Receiver::set_nonblocking`] changes the open file description, which is
+    /// shared with all other processes that have a copy of the pipe end, for
+    /// example a child process that inherited it. In emulated mode the file
+    /// descriptor flags are left untouched, instead `poll(2)` (with a zero
+    /// timeout) is used before each read to check if it would block, in which
+    /// case an error of kind [`WouldBlock`] is returned.
+    ///
+    /// # Notes
+    ///
+    /// This is only useful if the pipe is in blocking mode. If other processes
+    /// also read from the pipe, it's possible the read still blocks when
+    /// another process reads in between the check and the read.
+    ///
+    /// [`WouldBlock`]: io::ErrorKind::WouldBlock
+    #[cfg(unix)]
+    pub fn set_nonblocking_emulated(&self, emulate: bool) {
+        self.inner.set_nonblocking_emulated(emulate)
+    }
+
+    /// Returns `true` if non-blocking mode is emulated, see
+    /// [`Receiver::set_nonblocking_emulated`].
+    #[cfg(unix)]
+    pub fn is_nonblocking_emulated(&self) -> bool {
+        self.inner.is_nonblocking_emulated()
+    }
+
+    /// Set the policy for `EINTR` errors, see [`InterruptPolicy`].
+    ///
+    /// This applies to the `read` and `read_vectored` methods. Defaults to
+    /// [`InterruptPolicy::Surface`].
+    pub fn set_interrupt_policy(&self, policy: InterruptPolicy) {
+        self.inner.set_max_interrupt_retries(policy.max_retries())
+    }
+
+    /// Returns the policy for `EINTR` errors.
+    ///
+    /// This returns the normalised policy, see [`InterruptPolicy::RetryUpTo`].
+    pub fn interrupt_policy(&self) -> InterruptPolicy {
+        InterruptPolicy::from_max_retries(self.inner.max_interrupt_retries())
+    }
+
+    /// Returns `true` if the `Receiver` is in non-blocking mode.
+    #[cfg(unix)]
+    pub fn is_nonblocking(&self) -> io::Result<bool> {
+        self.flags().map(|flags| flags.is_nonblocking())
+    }
+
+    /// Set or clear `FD_CLOEXEC`, which closes the file descriptor when
+    /// executing another program.
+    ///
+    /// Pipes created by [`new_pipe`] have `FD_CLOEXEC` set, to pass a pipe end
+    /// to a child process see [`Receiver::into_inheritable`].
+    #[cfg(unix)]
+    pub fn set_cloexec(&self, cloexec: bool) -> io::Result<()> {
+        self.inner.set_cloexec(cloexec)
+    }
+
+    /// Returns `true` if `FD_CLOEXEC` is set.
+    #[cfg(unix)]
+    pub fn is_cloexec(&self) -> io::Result<bool> {
+        self.flags().map(|flags| flags.is_cloexec())
+    }
+
+    /// Returns the file descriptor flags.
+    #[cfg(unix)]
+    pub fn flags(&self) -> io::Result<Flags> {
+        self.inner.flags()
+    }
+
+    /// Wait until the `Receiver` is readable, or the `timeout` elapsed.
+    ///
+    /// Returns `false` if the timeout elapsed. This also returns `true` if all
+    /// `Sender`s were dropped, in which case reading returns zero bytes.
+    #[cfg(unix)]
+    pub fn wait_readable(&self, timeout: Duration) -> io::Result<bool> {
+        self.inner.wait_readable(Some(timeout))
+    }
+
+    /// Read into `buf`, waiting at most `timeout` for the pipe to become
+    /// readable.
+    ///
+    /// Works in both blocking and non-blocking mode. Returns an error of kind
+    /// [`TimedOut`] if the timeout elapsed.
+    ///
+    /// [`TimedOut`]: io::ErrorKind::TimedOut
+    #[cfg(unix)]
+    pub fn read_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        self.inner.read_timeout(buf, timeout)
+    }
+
+    /// Close the `Receiver`, returning any error returned by `close(2)`.
+    ///
+    /// Dropping the `Receiver` also closes it, but ignores any errors. Note that
+    /// the file descriptor is closed even if an error is returned, e.g. an
+    /// `EINTR` error should not be retried.
+    #[cfg(unix)]
+    pub fn close(self) -> io::Result<()> {
+        self.inner.close()
+    }
+
+    /// Create a `Receiver` from `fd`, checking that it's the read end of a pipe.
+    ///
+    /// Unlike the `From` and [`FromRawFd`] implementations this sets the pipe
+    /// to non-blocking mode and sets `FD_CLOEXEC`. Returns an error of kind
+    /// [`InvalidInput`] if `fd` is not a pipe (e.g. a socket or regular file)
+    /// or not opened for reading, in which case `fd` is closed.
+    ///
+    /// This accepts `ChildStdout` or `ChildStderr` directly. `TryFrom` can't be
+    /// implemented for those types, as it would conflict with the existing
+    /// `From` implementations.
+    ///
+    /// [`InvalidInput`]: io::ErrorKind::InvalidInput
+    #[cfg(unix)]
+    pub fn try_from_fd<F: Into<OwnedFd>>(fd: F) -> io::Result<Receiver> {
+        sys::Receiver::try_from_fd(fd.into()).map(|inner| Receiver { inner })
+    }
+
+    /// Convert the `Receiver` into a pipe end that can be passed to a child
+    /// process, see [`CommandExt::pass_fd`].
+    ///
+    /// This sets the pipe end into blocking mode.
+    ///
+    /// [`CommandExt::pass_fd`]: process::CommandExt::pass_fd
+    #[cfg(unix)]
+    pub fn into_inheritable(self) -> io::Result<process::Inheritable> {
+        process::Inheritable::new(self)
+    }
+
+    /// Create a `Receiver` from the file descriptor in the environment variable
+    /// `key`, e.g. passed by the parent process using [`CommandExt::pass_fd`].
+    ///
+    /// This checks that the file descriptor is the read end of a pipe and sets
+    /// it to non-blocking with `FD_CLOEXEC` set.
+    ///
+    /// [`CommandExt::pass_fd`]: process::CommandExt::pass_fd
+    ///
+    /// # Safety
+    ///
+    /// The returned `Receiver` takes ownership of the file descriptor. The caller
+    /// must ensure the file descriptor is not owned by anything else, i.e. it
+    /// was passed by the parent process and this is called only once for it.
+    /// Consider using [`Receiver::take_from_env`], which also removes the
+    /// environment variable.
+    #[cfg(unix)]
+    pub unsafe fn from_env<K: AsRef<OsStr>>(key: K) -> io::Result<Receiver> {
+        fd_from_env(key.as_ref())
+            .and_then(sys::Receiver::from_inherited_fd)
+            .map(|inner| Receiver { inner })
+    }
+
+    /// Same as [`Receiver::from_env`], but removes the environment variable
+    /// once the `Receiver` is created.
+    ///
+    /// # Safety
+    ///
+    /// See [`Receiver::from_env`].
+    #[cfg(unix)]
+    pub unsafe fn take_from_env<K: AsRef<OsStr>>(key: K) -> io::Result<Receiver> {
+        let key = key.as_ref();
+        let receiver = Receiver::from_env(key)?;
+        env::remove_var(key);
+        Ok(receiver)
+    }
+
+    /// Duplicate the file descriptor.
+    pub(crate) fn try_clone(&self) -> io::Result<Receiver> {
+        self.inner.try_clone().map(|inner| Receiver { inner })
+    }
 }
 
 impl event::Source for Receiver {
@@ -166,6 +620,16 @@ impl Read for Receiver {
     }
 }
 
+impl Read for &Receiver {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&self.inner).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        (&self.inner).read_vectored(bufs)
+    }
+}
+
 /// # Notes
 ///
 /// The underlying pipe is **not** set to non-blocking.
@@ -211,6 +675,170 @@ impl IntoRawFd for Receiver {
     }
 }
 
+#[cfg(unix)]
+impl AsFd for Receiver {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// # Notes
+///
+/// The file descriptor is **not** set to non-blocking, nor is it checked to be
+/// a pipe.
+#[cfg(unix)]
+impl From<OwnedFd> for Receiver {
+    fn from(fd: OwnedFd) -> Receiver {
+        Receiver {
+            inner: sys::Receiver::from(fd),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl From<Receiver> for OwnedFd {
+    fn from(receiver: Receiver) -> OwnedFd {
+        receiver.inner.into()
+    }
+}
+
+/// # Notes
+///
+/// The pipe is **not** set to blocking mode, which most programs expect for
+/// their standard streams, use [`Receiver::set_nonblocking`] to change it before
+/// converting.
+#[cfg(unix)]
+impl From<Receiver> for Stdio {
+    fn from(receiver: Receiver) -> Stdio {
+        Stdio::from(OwnedFd::from(receiver))
+    }
+}
+
+/// Policy for handling interrupted I/O (`EINTR` errors), see
+/// [`Sender::set_interrupt_policy`] and [`Receiver::set_interrupt_policy`].
+///
+/// I/O on a pipe end in blocking mode can be interrupted by a signal if the
+/// signal handler is installed without `SA_RESTART`, in which case an error of
+/// kind [`Interrupted`] is returned.
+///
+/// The policy only applies to I/O using [`Sender`] and [`Receiver`] directly.
+/// Helpers that frame data over pipes they own, such as
+/// [`readiness::Notifier::notify`], [`fork_server::ForkServer`],
+/// [`jobserver::Client::try_acquire`] and [`channel::bounded`], always retry
+/// interrupted I/O, as surfacing the error in the middle of a frame would leave
+/// the pipe in an inconsistent state.
+///
+/// [`Interrupted`]: io::ErrorKind::Interrupted
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum InterruptPolicy {
+    /// Return the error to the caller, this is the default.
+    #[default]
+    Surface,
+    /// Retry the I/O operation until it's no longer interrupted.
+    Retry,
+    /// Retry the I/O operation at most the given number of times, after
+    /// which the error is returned.
+    ///
+    /// `RetryUpTo(0)` is the same as `Surface` and `RetryUpTo(u32::MAX)` the
+    /// same as `Retry`, `interrupt_policy` returns those variants instead.
+    RetryUpTo(u32),
+}
+
+impl InterruptPolicy {
+    /// Returns the maximum number of retries, `u32::MAX` meaning no limit.
+    fn max_retries(self) -> u32 {
+        match self {
+            InterruptPolicy::Surface => 0,
+            InterruptPolicy::Retry => u32::MAX,
+            InterruptPolicy::RetryUpTo(retries) => retries,
+        }
+    }
+
+    fn from_max_retries(retries: u32) -> InterruptPolicy {
+        match retries {
+            0 => InterruptPolicy::Surface,
+            u32::MAX => InterruptPolicy::Retry,
+            retries => InterruptPolicy::RetryUpTo(retries),
+        }
+    }
+}
+
+/// Implements conversions between `Sender` and `Receiver` and the writer and
+/// reader types of other pipe implementations, which must implement
+/// `From<OwnedFd>` and `Into<OwnedFd>`.
+#[cfg(all(unix, any(feature = "std-pipe", feature = "os_pipe")))]
+macro_rules! impl_pipe_conversions {
+    ($reader: ty, $writer: ty) => {
+        /// Sets the pipe to non-blocking mode and `FD_CLOEXEC`.
+        impl TryFrom<$writer> for Sender {
+            type Error = io::Error;
+
+            fn try_from(writer: $writer) -> io::Result<Sender> {
+                sys::Sender::from_pipe_fd(writer.into()).map(|inner| Sender { inner })
+            }
+        }
+
+        /// Sets the pipe back to blocking mode.
+        impl TryFrom<Sender> for $writer {
+            type Error = io::Error;
+
+            fn try_from(sender: Sender) -> io::Result<$writer> {
+                sender.set_nonblocking(false)?;
+                Ok(<$writer>::from(OwnedFd::from(sender)))
+            }
+        }
+
+        /// Sets the pipe to non-blocking mode and `FD_CLOEXEC`.
+        impl TryFrom<$reader> for Receiver {
+            type Error = io::Error;
+
+            fn try_from(reader: $reader) -> io::Result<Receiver> {
+                sys::Receiver::from_pipe_fd(reader.into()).map(|inner| Receiver { inner })
+            }
+        }
+
+        /// Sets the pipe back to blocking mode.
+        impl TryFrom<Receiver> for $reader {
+            type Error = io::Error;
+
+            fn try_from(receiver: Receiver) -> io::Result<$reader> {
+                receiver.set_nonblocking(false)?;
+                Ok(<$reader>::from(OwnedFd::from(receiver)))
+            }
+        }
+    };
+}
+
+#[cfg(all(unix, feature = "std-pipe"))]
+impl_pipe_conversions!(std::io::PipeReader, std::io::PipeWriter);
+
+#[cfg(all(unix, feature = "os_pipe"))]
+impl_pipe_conversions!(os_pipe::PipeReader, os_pipe::PipeWriter);
+
+/// Parse a file descriptor from the environment variable `key`.
+#[cfg(unix)]
+fn fd_from_env(key: &OsStr) -> io::Result<RawFd> {
+    let value = env::var_os(key).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("environment variable {:?} not found", key),
+        )
+    })?;
+    value
+        .to_str()
+        .and_then(|value| value.trim().parse::<RawFd>().ok())
+        .filter(|fd| *fd >= 0)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "environment variable {:?} doesn't contain a file descriptor",
+                    key
+                ),
+            )
+        })
+}
+
 /// Create a new non-blocking Unix pipe.
 ///
 /// This is a wrapper around Unix's [`pipe(2)`] system call and can be used as
@@ -244,7 +872,8 @@ impl IntoRawFd for Receiver {
 /// # Deregistering
 ///
 /// Both `Sender` and `Receiver` will deregister themselves when dropped,
-/// **iff** the file descriptors are not duplicated (via [`dup(2)`]).
+/// **iff** the file descriptors are not duplicated (via [`dup(2)`]). Use
+/// [`Registered`] to always deregister when dropped.
 ///
 /// [`dup(2)`]: https://pubs.opengroup.org/onlinepubs/9699919799/functions/dup.html
 ///
@@ -349,3 +978,42 @@ pub fn new_pipe() -> io::Result<(Sender, Receiver)> {
     sys::new_pipe()
         .map(|(sender, receiver)| (Sender { inner: sender }, Receiver { inner: receiver }))
 }
+
+/// Wait until any of `ends` is ready, or the `timeout` elapsed.
+///
+/// Receiving ends are waited on until they're readable, sending ends until
+/// they're writable, this is determined by the access mode of the file
+/// descriptor (see [`Flags::access_mode`]). This makes it possible to wait on
+/// several pipe ends without setting up a [`Poll`] instance.
+///
+/// Returns the indices into `ends` of the ready pipe ends, or an empty vector
+/// if the timeout elapsed.
+///
+/// [`Poll`]: mio::Poll
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Write};
+/// use std::os::unix::io::AsRawFd;
+/// use std::time::Duration;
+///
+/// use mio_pipe::{new_pipe, wait_any};
+///
+/// # fn main() -> io::Result<()> {
+/// let (mut sender1, receiver1) = new_pipe()?;
+/// let (_sender2, receiver2) = new_pipe()?;
+///
+/// let ends: [&dyn AsRawFd; 2] = [&receiver1, &receiver2];
+/// assert!(wait_any(&ends, Duration::from_millis(10))?.is_empty());
+///
+/// sender1.write_all(b"hello")?;
+/// assert_eq!(wait_any(&ends, Duration::from_millis(10))?, vec![0]);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(unix)]
+pub fn wait_any(ends: &[&dyn AsRawFd], timeout: Duration) -> io::Result<Vec<usize>> {
+    let fds: Vec<RawFd> = ends.iter().map(|end| end.as_raw_fd()).collect();
+    sys::wait_any(&fds, Some(timeout))
+}
@@ -0,0 +1,437 @@
+//! Process related utilities.
+//!
+//! See [`CommandExt::pass_fd`] to pass pipes to child processes and
+//! [`spawn_raw`] to spawn processes without using [`Command`].
+
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::process::{CommandExt as _, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use crate::new_pipe;
+
+/// End of a pipe that can be inherited by a child process.
+///
+/// Created by [`Sender::into_inheritable`] or [`Receiver::into_inheritable`],
+/// and passed to a child process using [`CommandExt::pass_fd`].
+///
+/// Unlike the [`Sender`] and [`Receiver`] the file descriptor is in blocking
+/// mode, as most programs expect this. The file descriptor still has
+/// `FD_CLOEXEC` set, so it's not leaked into other child processes.
+///
+/// [`Sender`]: crate::Sender
+/// [`Sender::into_inheritable`]: crate::Sender::into_inheritable
+/// [`Receiver`]: crate::Receiver
+/// [`Receiver::into_inheritable`]: crate::Receiver::into_inheritable
+#[derive(Debug)]
+pub struct Inheritable {
+    inner: File,
+}
+
+impl Inheritable {
+    /// Create a new `Inheritable`, setting `fd` into blocking mode.
+    pub(crate) fn new<F>(fd: F) -> io::Result<Inheritable>
+    where
+        F: IntoRawFd,
+    {
+        let inner = unsafe { File::from_raw_fd(fd.into_raw_fd()) };
+        let value = 0 as libc::c_int;
+        if unsafe { libc::ioctl(inner.as_raw_fd(), libc::FIONBIO, &value) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(Inheritable { inner })
+        }
+    }
+}
+
+impl AsRawFd for Inheritable {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Inheritable {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+/// Extension trait for [`Command`].
+pub trait CommandExt {
+    /// Pass `end` to the child process as file descriptor `target_fd`.
+    ///
+    /// In the child process the pipe end is duplicated (using [`dup2(2)`]) to
+    /// `target_fd` with `FD_CLOEXEC` cleared, the file descriptor in the parent
+    /// process is not affected. The pipe end is closed in the parent process
+    /// once the `Command` is dropped.
+    ///
+    /// The child process is not told about the file descriptor, use an
+    /// argument or environment variable for that. For the latter the child
+    /// process can use [`Receiver::from_env`] and [`Sender::from_env`].
+    ///
+    /// [`dup2(2)`]: https://pubs.opengroup.org/onlinepubs/9699919799/functions/dup2.html
+    /// [`Receiver::from_env`]: crate::Receiver::from_env
+    /// [`Sender::from_env`]: crate::Sender::from_env
+    ///
+    /// # Notes
+    ///
+    /// Pipe ends can be passed onto each other's file descriptors, e.g. the
+    /// end on file descriptor 4 to 3 and the end on 3 to 4. Passing 0, 1 or 2
+    /// as `target_fd` overwrites the standard I/O set by [`Command`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::{self, Read};
+    /// use std::process::Command;
+    ///
+    /// use mio_pipe::new_pipe;
+    /// use mio_pipe::process::CommandExt;
+    ///
+    /// # fn main() -> io::Result<()> {
+    /// let (sender, mut receiver) = new_pipe()?;
+    ///
+    /// let mut cmd = Command::new("sh");
+    /// cmd.args(["-c", "echo 'Hello world' >&3"])
+    ///     .pass_fd(3, sender.into_inheritable()?);
+    /// cmd.status()?;
+    /// // Drop the `Command` to close our copy of the sending end.
+    /// drop(cmd);
+    ///
+    /// let mut output = String::new();
+    /// receiver.set_nonblocking(false)?;
+    /// receiver.read_to_string(&mut output)?;
+    /// assert_eq!(output, "Hello world\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn pass_fd(&mut self, target_fd: RawFd, end: Inheritable) -> &mut Self;
+}
+
+impl CommandExt for Command {
+    fn pass_fd(&mut self, target_fd: RawFd, end: Inheritable) -> &mut Self {
+        // The pipe ends are duplicated to their target in the order in which
+        // `pass_fd` is called. To ensure a previous `dup2` doesn't overwrite
+        // this end, move it above all targets passed so far.
+        let max_target = MAX_TARGET_FD.fetch_max(target_fd, Ordering::AcqRel);
+        let end = move_above(end, max_target.max(target_fd));
+        unsafe {
+            // NOTE: only async-signal-safe functions can be used here.
+            self.pre_exec(move || {
+                let fd = match &end {
+                    Ok(end) => end.as_raw_fd(),
+                    Err(errno) => return Err(io::Error::from_raw_os_error(*errno)),
+                };
+                if fd == target_fd {
+                    clear_cloexec(fd)
+                } else if libc::dup2(fd, target_fd) == -1 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    // `dup2(2)` doesn't copy `FD_CLOEXEC`.
+                    Ok(())
+                }
+            })
+        }
+    }
+}
+
+/// Highest `target_fd` passed to [`CommandExt::pass_fd`].
+static MAX_TARGET_FD: AtomicI32 = AtomicI32::new(2);
+
+/// Move `end` to a file descriptor higher than `min_fd`, returning the error
+/// number on error.
+fn move_above(end: Inheritable, min_fd: RawFd) -> Result<Inheritable, i32> {
+    if end.as_raw_fd() > min_fd {
+        return Ok(end);
+    }
+    match unsafe { libc::fcntl(end.as_raw_fd(), libc::F_DUPFD_CLOEXEC, min_fd + 1) } {
+        -1 => Err(io::Error::last_os_error()
+            .raw_os_error()
+            .unwrap_or(libc::EBADF)),
+        fd => Ok(Inheritable {
+            inner: unsafe { File::from_raw_fd(fd) },
+        }),
+    }
+}
+
+/// Clear the `FD_CLOEXEC` flag on `fd`.
+pub(crate) fn clear_cloexec(fd: RawFd) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) } == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Spawn a new process using `fork(2)` and `execve(2)`.
+///
+/// This executes `program` (which is **not** searched for in `PATH`) with
+/// `args` as arguments, note that `args` must include the program name as first
+/// argument (`argv[0]`). If `env` is `None` the environment of the current
+/// process is used.
+///
+/// Between the `fork(2)` and `execve(2)` calls `pre_exec` is called in the
+/// child process, this can be used to e.g. call `setsid(2)`, `unshare(2)` or
+/// `close_range(2)`.
+///
+/// Any error in the child process, either returned by `pre_exec` or by
+/// `execve(2)`, is reported back to the parent process using a pipe (created
+/// by [`new_pipe`]) with `FD_CLOEXEC` set. If the pipe is closed without any
+/// data being written it means `execve(2)` succeeded. On error the child
+/// process is reaped.
+///
+/// [`new_pipe`]: crate::new_pipe
+///
+/// # Safety
+///
+/// Same as [`std::os::unix::process::CommandExt::pre_exec`], the `pre_exec`
+/// function is called in the child process after `fork(2)`, where only
+/// [async-signal-safe] functions may be used. Most notably it may not allocate
+/// or acquire any locks.
+///
+/// [async-signal-safe]: https://man7.org/linux/man-pages/man7/signal-safety.7.html
+///
+/// # Examples
+///
+/// ```
+/// use std::ffi::CStr;
+/// use std::io;
+///
+/// use mio_pipe::process::{spawn_raw, Stage};
+///
+/// # fn main() -> io::Result<()> {
+/// let cstr = |s: &'static [u8]| CStr::from_bytes_with_nul(s).unwrap();
+/// let program = cstr(b"/bin/sh\0");
+/// let args = [cstr(b"sh\0"), cstr(b"-c\0"), cstr(b"exit 0\0")];
+///
+/// let mut child = unsafe { spawn_raw(program, &args, None, || Ok(()))? };
+/// assert!(child.wait()?.success());
+///
+/// // Errors are reported in the parent process.
+/// let program = cstr(b"/does/not/exist\0");
+/// let err = unsafe { spawn_raw(program, &[program], None, || Ok(())) }.unwrap_err();
+/// assert_eq!(err.stage(), Stage::Exec);
+/// assert_eq!(err.error().kind(), io::ErrorKind::NotFound);
+/// # Ok(())
+/// # }
+/// ```
+pub unsafe fn spawn_raw<F>(
+    program: &CStr,
+    args: &[&CStr],
+    env: Option<&[&CStr]>,
+    mut pre_exec: F,
+) -> Result<RawChild, SpawnError>
+where
+    F: FnMut() -> io::Result<()>,
+{
+    // All allocations must be done before forking.
+    let argv = to_ptrs(args);
+    let envp = env.map(to_ptrs);
+
+    let (sender, mut receiver) = new_pipe().map_err(|err| SpawnError::new(Stage::Fork, err))?;
+    receiver
+        .set_nonblocking(false)
+        .map_err(|err| SpawnError::new(Stage::Fork, err))?;
+
+    match libc::fork() {
+        -1 => Err(SpawnError::new(Stage::Fork, io::Error::last_os_error())),
+        0 => {
+            // Child process.
+            let fd = sender.as_raw_fd();
+            if let Err(err) = pre_exec() {
+                report_error(fd, Stage::PreExec, &err);
+            }
+            match &envp {
+                Some(envp) => libc::execve(program.as_ptr(), argv.as_ptr(), envp.as_ptr()),
+                None => libc::execv(program.as_ptr(), argv.as_ptr()),
+            };
+            report_error(fd, Stage::Exec, &io::Error::last_os_error())
+        }
+        pid => {
+            // Parent process, we need to drop our write end to be able to
+            // detect the child closing it.
+            drop(sender);
+            let mut child = RawChild { pid };
+
+            let mut buf = [0; 8];
+            let mut n = 0;
+            while n < buf.len() {
+                match receiver.read(&mut buf[n..]) {
+                    Ok(0) => break,
+                    Ok(read) => n += read,
+                    Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => {
+                        let _ = child.wait();
+                        return Err(SpawnError::new(Stage::Exec, err));
+                    }
+                }
+            }
+
+            match n {
+                // Pipe closed without an error, `execve(2)` was successful.
+                0 => Ok(child),
+                8 => {
+                    let _ = child.wait();
+                    let mut stage = [0; 4];
+                    let mut errno = [0; 4];
+                    stage.copy_from_slice(&buf[..4]);
+                    errno.copy_from_slice(&buf[4..]);
+                    let stage = if i32::from_ne_bytes(stage) == Stage::PreExec as i32 {
+                        Stage::PreExec
+                    } else {
+                        Stage::Exec
+                    };
+                    let err = io::Error::from_raw_os_error(i32::from_ne_bytes(errno));
+                    Err(SpawnError::new(stage, err))
+                }
+                _ => {
+                    let _ = child.wait();
+                    let err = io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "short read on error pipe of child process",
+                    );
+                    Err(SpawnError::new(Stage::Exec, err))
+                }
+            }
+        }
+    }
+}
+
+/// Create a null terminated array of pointers to `strings`.
+fn to_ptrs(strings: &[&CStr]) -> Vec<*const libc::c_char> {
+    strings
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain(Some(ptr::null()))
+        .collect()
+}
+
+/// Report `err` to the parent process via `fd` and exit.
+///
+/// This is called in the child process after `fork(2)` and thus may only call
+/// async-signal-safe functions.
+unsafe fn report_error(fd: RawFd, stage: Stage, err: &io::Error) -> ! {
+    let errno = err.raw_os_error().unwrap_or(libc::EINVAL);
+    let mut buf = [0; 8];
+    buf[..4].copy_from_slice(&(stage as i32).to_ne_bytes());
+    buf[4..].copy_from_slice(&errno.to_ne_bytes());
+    // The pipe is empty, so this write of less than `PIPE_BUF` bytes can't
+    // be short.
+    let _ = libc::write(fd, buf.as_ptr().cast(), buf.len());
+    libc::_exit(127)
+}
+
+/// Child process spawned by [`spawn_raw`].
+///
+/// Unlike [`std::process::Child`] the process is **not** reaped automatically,
+/// [`RawChild::wait`] must be called to reap it.
+#[derive(Debug)]
+pub struct RawChild {
+    pid: libc::pid_t,
+}
+
+impl RawChild {
+    /// Returns the process id of the child process.
+    pub fn id(&self) -> u32 {
+        self.pid as u32
+    }
+
+    /// Wait for the child process to exit.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.waitpid(0).map(|status| status.unwrap())
+    }
+
+    /// Check if the child process exited, without blocking.
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.waitpid(libc::WNOHANG)
+    }
+
+    fn waitpid(&mut self, options: libc::c_int) -> io::Result<Option<ExitStatus>> {
+        let mut status = 0;
+        loop {
+            match unsafe { libc::waitpid(self.pid, &mut status, options) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => return Ok(None),
+                _ => return Ok(Some(ExitStatus::from_raw(status))),
+            }
+        }
+    }
+}
+
+/// Stage at which [`spawn_raw`] failed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Stage {
+    /// Failed to create the error pipe or to fork the process.
+    Fork = 0,
+    /// The `pre_exec` function returned an error.
+    PreExec = 1,
+    /// Failed to execute the program.
+    Exec = 2,
+}
+
+/// Error returned by [`spawn_raw`].
+#[derive(Debug)]
+pub struct SpawnError {
+    stage: Stage,
+    error: io::Error,
+}
+
+impl SpawnError {
+    fn new(stage: Stage, error: io::Error) -> SpawnError {
+        SpawnError { stage, error }
+    }
+
+    /// Returns the stage at which the spawning failed.
+    pub fn stage(&self) -> Stage {
+        self.stage
+    }
+
+    /// Returns the underlying I/O error.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Returns the underlying I/O error.
+    pub fn into_error(self) -> io::Error {
+        self.error
+    }
+}
+
+impl From<SpawnError> for io::Error {
+    fn from(err: SpawnError) -> io::Error {
+        err.error
+    }
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stage = match self.stage {
+            Stage::Fork => "fork",
+            Stage::PreExec => "pre-exec",
+            Stage::Exec => "exec",
+        };
+        write!(
+            f,
+            "failed to spawn process ({} stage): {}",
+            stage, self.error
+        )
+    }
+}
+
+impl Error for SpawnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,369 @@
+//! Pseudo-terminals.
+//!
+//! See [`new_pty`].
+
+use std::ffi::{CStr, OsStr};
+use std::fs::{File, OpenOptions};
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use mio::unix::SourceFd;
+use mio::{event, Interest, Registry, Token};
+
+use crate::sys;
+
+/// Create a new pseudo-terminal.
+///
+/// The master end is set to non-blocking and can be registered with
+/// [`Poll`], the slave end is left in blocking mode as it's meant to be used
+/// as standard in, out and error of a child process, see
+/// [`PtySlave::configure`]. Both ends have `FD_CLOEXEC` set, same as the ends
+/// of a pipe created by [`new_pipe`].
+///
+/// [`Poll`]: mio::Poll
+/// [`new_pipe`]: crate::new_pipe
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Read};
+/// use std::process::Command;
+///
+/// use mio_pipe::pty::new_pty;
+///
+/// # fn main() -> io::Result<()> {
+/// let (master, slave) = new_pty()?;
+///
+/// let mut cmd = Command::new("sh");
+/// cmd.args(["-c", "test -t 1 && echo tty"]);
+/// slave.configure(&mut cmd)?;
+/// let mut child = cmd.spawn()?;
+/// // Close our copies of the slave end, so we read end of file once the child
+/// // exits.
+/// drop(cmd);
+/// drop(slave);
+/// child.wait()?;
+///
+/// master.set_nonblocking(false)?;
+/// let mut output = String::new();
+/// (&master).read_to_string(&mut output)?;
+/// assert_eq!(output, "tty\r\n");
+/// # Ok(())
+/// # }
+/// ```
+pub fn new_pty() -> io::Result<(PtyMaster, PtySlave)> {
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: just opened the file descriptor above.
+    let master = PtyMaster {
+        inner: unsafe { File::from_raw_fd(fd) },
+    };
+
+    if unsafe { libc::grantpt(fd) } == -1 || unsafe { libc::unlockpt(fd) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let path = slave_path(fd)?;
+    let inner = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(&path)?;
+    sys::set_nonblocking(fd, true)?;
+    Ok((master, PtySlave { inner, path }))
+}
+
+/// Returns the path to the slave end of the master `fd`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn slave_path(fd: RawFd) -> io::Result<PathBuf> {
+    let mut buf = [0; 128];
+    match unsafe { libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len()) } {
+        0 => {
+            let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+            Ok(Path::new(OsStr::from_bytes(name.to_bytes())).to_owned())
+        }
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Returns the path to the slave end of the master `fd`.
+#[cfg(not(any(target_os = "android", target_os = "linux")))]
+fn slave_path(fd: RawFd) -> io::Result<PathBuf> {
+    // NOTE: `ptsname(3)` isn't thread-safe, but `ptsname_r` isn't available.
+    let name = unsafe { libc::ptsname(fd) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let name = unsafe { CStr::from_ptr(name) };
+    Ok(Path::new(OsStr::from_bytes(name.to_bytes())).to_owned())
+}
+
+/// Size of a terminal window, see [`PtyMaster::set_window_size`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct WindowSize {
+    /// Number of rows.
+    pub rows: u16,
+    /// Number of columns.
+    pub cols: u16,
+}
+
+/// Master end of a pseudo-terminal, see [`new_pty`].
+///
+/// Writing to the master end is the same as typing on the terminal, reading
+/// returns what the child process wrote to the terminal.
+///
+/// # Notes
+///
+/// On Linux reading from the master end returns an `EIO` error once all
+/// slave ends are closed, the `Read` implementation returns `Ok(0)` instead,
+/// same as the receiving end of a pipe.
+#[derive(Debug)]
+pub struct PtyMaster {
+    inner: File,
+}
+
+impl PtyMaster {
+    /// Set the master end in non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        sys::set_nonblocking(self.inner.as_raw_fd(), nonblocking)
+    }
+
+    /// Duplicate the master end.
+    pub fn try_clone(&self) -> io::Result<PtyMaster> {
+        self.inner.try_clone().map(|inner| PtyMaster { inner })
+    }
+
+    /// Set the window size of the terminal.
+    ///
+    /// This sends `SIGWINCH` to the foreground process group of the terminal.
+    pub fn set_window_size(&self, size: WindowSize) -> io::Result<()> {
+        let size = libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        if unsafe { libc::ioctl(self.inner.as_raw_fd(), libc::TIOCSWINSZ, &size) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the window size of the terminal.
+    pub fn window_size(&self) -> io::Result<WindowSize> {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        if unsafe { libc::ioctl(self.inner.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(WindowSize {
+                rows: size.ws_row,
+                cols: size.ws_col,
+            })
+        }
+    }
+}
+
+/// Map the `EIO` error returned once the slave is closed to end of file.
+fn eio_to_eof(res: io::Result<usize>) -> io::Result<usize> {
+    match res {
+        Err(ref err) if err.raw_os_error() == Some(libc::EIO) => Ok(0),
+        res => res,
+    }
+}
+
+impl event::Source for PtyMaster {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.inner.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.inner.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        SourceFd(&self.inner.as_raw_fd()).deregister(registry)
+    }
+}
+
+impl Read for PtyMaster {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        (&*self).read_vectored(bufs)
+    }
+}
+
+impl Read for &PtyMaster {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        eio_to_eof((&self.inner).read(buf))
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        eio_to_eof((&self.inner).read_vectored(bufs))
+    }
+}
+
+impl Write for PtyMaster {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        (&*self).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Write for &PtyMaster {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&self.inner).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        (&self.inner).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.inner).flush()
+    }
+}
+
+impl AsRawFd for PtyMaster {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for PtyMaster {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
+
+/// Slave end of a pseudo-terminal, see [`new_pty`].
+#[derive(Debug)]
+pub struct PtySlave {
+    inner: File,
+    path: PathBuf,
+}
+
+impl PtySlave {
+    /// Path to the slave end, e.g. `/dev/pts/3`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Duplicate the slave end.
+    pub fn try_clone(&self) -> io::Result<PtySlave> {
+        self.inner.try_clone().map(|inner| PtySlave {
+            inner,
+            path: self.path.clone(),
+        })
+    }
+
+    /// Configure `cmd` to use the terminal as standard in, out and error, and
+    /// as controlling terminal in a new session.
+    ///
+    /// The `Command` must be dropped after spawning the process, as it holds
+    /// copies of the slave end.
+    pub fn configure(&self, cmd: &mut Command) -> io::Result<()> {
+        let _ = cmd
+            .stdin(self.try_clone()?)
+            .stdout(self.try_clone()?)
+            .stderr(self.try_clone()?);
+        unsafe {
+            let _ = cmd.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Standard in is the slave end at this point.
+                if libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Put the terminal in raw mode, disabling input processing (such as
+    /// echoing and line editing) and output processing.
+    ///
+    /// See [`cfmakeraw(3)`].
+    ///
+    /// [`cfmakeraw(3)`]: https://man7.org/linux/man-pages/man3/cfmakeraw.3.html
+    pub fn set_raw_mode(&self) -> io::Result<()> {
+        let fd = self.inner.as_raw_fd();
+        let mut termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        unsafe { libc::cfmakeraw(&mut termios) };
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Read for PtySlave {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for PtySlave {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl From<PtySlave> for Stdio {
+    fn from(slave: PtySlave) -> Stdio {
+        Stdio::from(slave.inner)
+    }
+}
+
+impl AsRawFd for PtySlave {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for PtySlave {
+    fn into_raw_fd(self) -> RawFd {
+        self.inner.into_raw_fd()
+    }
+}
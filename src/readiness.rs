@@ -0,0 +1,249 @@
+//! Service readiness notification.
+//!
+//! This implements the readiness notification protocol used by [s6] (and
+//! others): the supervisor passes the write end of a pipe to the service, once
+//! the service is ready it writes a newline to the pipe and closes it. If the
+//! service exits before becoming ready the supervisor will read end of file
+//! instead.
+//!
+//! The supervisor side is implemented by [`Waiter`], the service side by
+//! [`Notifier`].
+//!
+//! [s6]: https://skarnet.org/software/s6/notifywhenup.html
+//!
+//! # Examples
+//!
+//! ```
+//! use std::io;
+//! use std::process::Command;
+//! use std::time::Duration;
+//!
+//! use mio_pipe::readiness::{Status, Waiter};
+//!
+//! # fn main() -> io::Result<()> {
+//! let mut waiter = Waiter::new()?;
+//!
+//! // Service that notifies readiness on file descriptor 3.
+//! let mut cmd = Command::new("sh");
+//! cmd.args(["-c", "echo >&3"]);
+//! waiter.configure(&mut cmd, 3)?;
+//! let mut child = cmd.spawn()?;
+//! // Drop the `Command` to close our copy of the pipe.
+//! drop(cmd);
+//!
+//! let status = waiter.wait(Some(Duration::from_secs(5)))?;
+//! assert_eq!(status, Status::Ready);
+//! # child.wait()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use mio::{event, Interest, Registry, Token};
+
+use crate::process::CommandExt;
+use crate::{new_pipe, sys, Receiver, Sender};
+
+/// Environment variable set by [`Waiter::configure`] and used by
+/// [`Notifier::from_env`].
+pub const ENV_VAR: &str = "NOTIFY_FD";
+
+/// Status of the service, returned by [`Waiter::wait`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Status {
+    /// The service notified it's ready.
+    Ready,
+    /// The service closed the pipe without notifying, usually this means the
+    /// service exited.
+    Exited,
+    /// The timeout elapsed before the service was ready.
+    TimedOut,
+}
+
+/// Supervisor side of the readiness notification.
+///
+/// See the [module documentation] for more.
+///
+/// The `Waiter` can be registered with [`READABLE`] interest, after receiving
+/// an event [`Waiter::try_status`] can be used to get the status of the
+/// service.
+///
+/// [module documentation]: crate::readiness
+/// [`READABLE`]: Interest::READABLE
+#[derive(Debug)]
+pub struct Waiter {
+    receiver: Receiver,
+    /// Write end, passed to the service in `configure`.
+    sender: Option<Sender>,
+    /// Status once the service is ready or exited.
+    status: Option<Status>,
+}
+
+impl Waiter {
+    /// Create a new `Waiter`.
+    pub fn new() -> io::Result<Waiter> {
+        let (sender, receiver) = new_pipe()?;
+        Ok(Waiter {
+            receiver,
+            sender: Some(sender),
+            status: None,
+        })
+    }
+
+    /// Configure `cmd` to pass the notification pipe as file descriptor `fd`.
+    ///
+    /// This also sets the [`ENV_VAR`] environment variable to `fd`.
+    ///
+    /// The `Command` must be dropped after spawning the process, otherwise the
+    /// `Waiter` can't detect that the service exited. This can only be called
+    /// once.
+    pub fn configure(&mut self, cmd: &mut Command, fd: RawFd) -> io::Result<()> {
+        let sender = self
+            .sender
+            .take()
+            .ok_or_else(|| io::Error::other("readiness `Waiter` already configured"))?;
+        let _ = cmd
+            .pass_fd(fd, sender.into_inheritable()?)
+            .env(ENV_VAR, fd.to_string());
+        Ok(())
+    }
+
+    /// Take the write end of the notification pipe, e.g. to pass it to a
+    /// service in another way than [`Waiter::configure`].
+    ///
+    /// Returns `None` if the `Waiter` is already configured.
+    pub fn take_sender(&mut self) -> Option<Sender> {
+        self.sender.take()
+    }
+
+    /// Get the status of the service, without blocking.
+    ///
+    /// Returns `Ok(None)` if the service is not yet ready (and hasn't exited).
+    pub fn try_status(&mut self) -> io::Result<Option<Status>> {
+        if self.status.is_some() {
+            return Ok(self.status);
+        }
+
+        let mut buf = [0; 64];
+        loop {
+            match self.receiver.read(&mut buf) {
+                Ok(0) => {
+                    self.status = Some(Status::Exited);
+                    return Ok(self.status);
+                }
+                Ok(n) if buf[..n].contains(&b'\n') => {
+                    self.status = Some(Status::Ready);
+                    return Ok(self.status);
+                }
+                // Ignore anything but the newline.
+                Ok(_) => continue,
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Wait for the service to become ready, or exit, with an optional
+    /// `timeout`.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<Status> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(status) = self.try_status()? {
+                return Ok(status);
+            }
+
+            let timeout =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            let mut fds = [libc::pollfd {
+                fd: self.receiver.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            }];
+            if sys::poll(&mut fds, timeout)? == 0 {
+                return Ok(Status::TimedOut);
+            }
+        }
+    }
+}
+
+impl event::Source for Waiter {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.receiver.deregister(registry)
+    }
+}
+
+/// Service side of the readiness notification.
+///
+/// See the [module documentation] for more.
+///
+/// [module documentation]: crate::readiness
+#[derive(Debug)]
+pub struct Notifier {
+    sender: Sender,
+}
+
+impl Notifier {
+    /// Create a `Notifier` from the file descriptor in the [`ENV_VAR`]
+    /// environment variable, removing the variable.
+    ///
+    /// See [`Sender::take_from_env`].
+    ///
+    /// # Safety
+    ///
+    /// The `Notifier` takes ownership of the file descriptor, see
+    /// [`Sender::from_env`].
+    pub unsafe fn from_env() -> io::Result<Notifier> {
+        Sender::take_from_env(ENV_VAR).map(Notifier::from)
+    }
+
+    /// Create a `Notifier` from the inherited file descriptor `fd`, e.g. when
+    /// the notification file descriptor is fixed in the service's
+    /// configuration.
+    ///
+    /// Same as [`Sender::try_from_fd`] this checks that `fd` is the write end
+    /// of a pipe, closing it if it's not.
+    pub fn from_fd(fd: OwnedFd) -> io::Result<Notifier> {
+        Sender::try_from_fd(fd).map(Notifier::from)
+    }
+
+    /// Notify the supervisor the service is ready.
+    pub fn notify(mut self) -> io::Result<()> {
+        loop {
+            match self.sender.write(b"\n") {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(_) => return Ok(()),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl From<Sender> for Notifier {
+    fn from(sender: Sender) -> Notifier {
+        Notifier { sender }
+    }
+}
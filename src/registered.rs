@@ -0,0 +1,160 @@
+use std::io;
+
+use mio::{event, Interest, Registry, Token};
+
+/// Event source registered with a [`Registry`], deregistering on drop.
+///
+/// As noted in the [`new_pipe`] documentation a pipe end is only removed from
+/// the `Poll` instance when it's dropped if the file descriptor wasn't
+/// duplicated, e.g. by forking. `Registered` explicitly deregisters the event
+/// source when it's dropped, so no stale registration is left behind.
+///
+/// `Registered` also remembers the current interests, so they can be changed
+/// using [`Registered::add_interest`] and [`Registered::remove_interest`],
+/// e.g. to only be interested in [`WRITABLE`] events while there is data to
+/// write.
+///
+/// [`new_pipe`]: crate::new_pipe
+/// [`WRITABLE`]: Interest::WRITABLE
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Write};
+///
+/// use mio::{Interest, Poll, Token};
+/// use mio_pipe::{new_pipe, Registered};
+///
+/// # fn main() -> io::Result<()> {
+/// let poll = Poll::new()?;
+/// let (sender, receiver) = new_pipe()?;
+///
+/// let mut sender = Registered::new(poll.registry(), sender, Token(0), Interest::WRITABLE)?;
+/// sender.get_mut().write_all(b"hello")?;
+/// // Only interested in writable events while we have something to write.
+/// sender.remove_interest(Interest::WRITABLE)?;
+/// assert_eq!(sender.interests(), None);
+/// sender.add_interest(Interest::WRITABLE)?;
+///
+/// // Deregisters the sender.
+/// drop(sender);
+/// # drop(receiver);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Registered<T: event::Source> {
+    /// Always `Some`, until `deregister` is called.
+    source: Option<T>,
+    registry: Registry,
+    token: Token,
+    /// `None` if the source is not registered, after all interests were
+    /// removed.
+    interests: Option<Interest>,
+}
+
+impl<T: event::Source> Registered<T> {
+    /// Register `source` with `registry`.
+    pub fn new(
+        registry: &Registry,
+        mut source: T,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<Registered<T>> {
+        let registry = registry.try_clone()?;
+        registry.register(&mut source, token, interests)?;
+        Ok(Registered {
+            source: Some(source),
+            registry,
+            token,
+            interests: Some(interests),
+        })
+    }
+
+    /// Returns the token of the event source.
+    pub fn token(&self) -> Token {
+        self.token
+    }
+
+    /// Returns the current interests, or `None` if all interests were removed.
+    pub fn interests(&self) -> Option<Interest> {
+        self.interests
+    }
+
+    /// Change the interests to `interests`.
+    pub fn reregister_interest(&mut self, interests: Interest) -> io::Result<()> {
+        let source = self.source.as_mut().unwrap();
+        if self.interests.is_some() {
+            self.registry.reregister(source, self.token, interests)?;
+        } else {
+            self.registry.register(source, self.token, interests)?;
+        }
+        self.interests = Some(interests);
+        Ok(())
+    }
+
+    /// Add `interest` to the current interests.
+    pub fn add_interest(&mut self, interest: Interest) -> io::Result<()> {
+        let interests = match self.interests {
+            Some(interests) => interests.add(interest),
+            None => interest,
+        };
+        self.reregister_interest(interests)
+    }
+
+    /// Remove `interest` from the current interests.
+    ///
+    /// If no interests are left the event source is deregistered, until an
+    /// interest is added again.
+    pub fn remove_interest(&mut self, interest: Interest) -> io::Result<()> {
+        match self
+            .interests
+            .and_then(|interests| interests.remove(interest))
+        {
+            Some(interests) => self.reregister_interest(interests),
+            None if self.interests.is_some() => {
+                self.registry.deregister(self.source.as_mut().unwrap())?;
+                self.interests = None;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a reference to the event source.
+    pub fn get_ref(&self) -> &T {
+        self.source.as_ref().unwrap()
+    }
+
+    /// Returns a mutable reference to the event source.
+    ///
+    /// The event source must not be deregistered or reregistered using the
+    /// returned reference.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.source.as_mut().unwrap()
+    }
+
+    /// Deregister the event source, returning it.
+    ///
+    /// Unlike dropping the `Registered` this returns deregistration errors.
+    /// On error the `Registered` is returned along with the error, so the
+    /// event source isn't lost.
+    pub fn deregister(mut self) -> Result<T, (io::Error, Registered<T>)> {
+        if self.interests.is_some() {
+            if let Err(err) = self.registry.deregister(self.source.as_mut().unwrap()) {
+                return Err((err, self));
+            }
+            self.interests = None;
+        }
+        Ok(self.source.take().unwrap())
+    }
+}
+
+impl<T: event::Source> Drop for Registered<T> {
+    fn drop(&mut self) {
+        if let (Some(source), Some(_)) = (self.source.as_mut(), self.interests) {
+            // Nothing we can do about errors at this point.
+            let _ = self.registry.deregister(source);
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Message preserving alternative to pipes, using `SOCK_SEQPACKET` sockets.
+//!
+//! See [`new_seqpacket_pair`].
+
+use std::fs::File;
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+
+use mio::unix::SourceFd;
+use mio::{event, Interest, Registry, Token};
+
+use crate::sys;
+
+/// Create a new connected pair of `SOCK_SEQPACKET` Unix sockets, used as a
+/// message preserving pipe.
+///
+/// The returned [`Sender`] and [`Receiver`] have the same API as the ends of
+/// a pipe created by [`new_pipe`]: both are in non-blocking mode, have
+/// `FD_CLOEXEC` set and can be registered with [`Poll`].
+///
+/// Unlike a pipe every write is received as a single message by a single
+/// read, even if it's larger than `PIPE_BUF`. The maximum size of a message
+/// is limited by the send buffer size of the socket (`SO_SNDBUF`), larger
+/// writes return an error.
+///
+/// [`new_pipe`]: crate::new_pipe
+/// [`Poll`]: mio::Poll
+///
+/// # Examples
+///
+/// ```
+/// use std::io::{self, Read, Write};
+///
+/// use mio_pipe::seqpacket::new_seqpacket_pair;
+///
+/// # fn main() -> io::Result<()> {
+/// let (mut sender, mut receiver) = new_seqpacket_pair()?;
+///
+/// sender.write_all(b"hello")?;
+/// sender.write_all(b"world")?;
+///
+/// // Every read returns a single message.
+/// let mut buf = [0; 64];
+/// let n = receiver.read(&mut buf)?;
+/// assert_eq!(&buf[..n], b"hello");
+/// let n = receiver.read(&mut buf)?;
+/// assert_eq!(&buf[..n], b"world");
+///
+/// // Once the sending half is shut down the receiver reads end of file.
+/// sender.shutdown()?;
+/// assert_eq!(receiver.read(&mut buf)?, 0);
+/// # Ok(())
+/// # }
+/// ```
+pub fn new_seqpacket_pair() -> io::Result<(Sender, Receiver)> {
+    let mut fds: [RawFd; 2] = [-1, -1];
+    let ty = libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK;
+    if unsafe { libc::socketpair(libc::AF_UNIX, ty, 0, fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: `socketpair(2)` ensures both file descriptors are valid.
+    unsafe { Ok((Sender::from_raw_fd(fds[0]), Receiver::from_raw_fd(fds[1]))) }
+}
+
+/// Sending end of a `SOCK_SEQPACKET` socket pair.
+///
+/// See [`new_seqpacket_pair`] for documentation, including examples.
+#[derive(Debug)]
+pub struct Sender {
+    inner: File,
+}
+
+impl Sender {
+    /// Set the socket in non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        sys::set_nonblocking(self.inner.as_raw_fd(), nonblocking)
+    }
+
+    /// Duplicate the `Sender`.
+    pub fn try_clone(&self) -> io::Result<Sender> {
+        self.inner.try_clone().map(|inner| Sender { inner })
+    }
+
+    /// Shut down the sending half of the socket.
+    ///
+    /// Once all messages are read the [`Receiver`] reads end of file, same as
+    /// if the `Sender` was dropped. Unlike dropping this also affects
+    /// duplicates of the `Sender`, e.g. in child processes.
+    pub fn shutdown(&self) -> io::Result<()> {
+        shutdown(self.inner.as_raw_fd(), libc::SHUT_WR)
+    }
+}
+
+impl Write for Sender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        (&*self).write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+/// Every write sends a single message, all of `buf` is written or an error is
+/// returned.
+impl Write for &Sender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    /// Sends all buffers as a single message.
+    ///
+    /// Empty writes don't send anything, as the `Receiver` would read an empty
+    /// message as end of file.
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        if bufs.iter().all(|buf| buf.is_empty()) {
+            return Ok(0);
+        }
+
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = bufs.as_ptr() as *mut libc::iovec;
+        msg.msg_iovlen = bufs.len() as _;
+        // Don't raise `SIGPIPE` if the receiver is closed, return `EPIPE`
+        // instead.
+        let n = unsafe { libc::sendmsg(self.inner.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) };
+        if n == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Receiving end of a `SOCK_SEQPACKET` socket pair.
+///
+/// See [`new_seqpacket_pair`] for documentation, including examples.
+#[derive(Debug)]
+pub struct Receiver {
+    inner: File,
+}
+
+impl Receiver {
+    /// Set the socket in non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        sys::set_nonblocking(self.inner.as_raw_fd(), nonblocking)
+    }
+
+    /// Duplicate the `Receiver`.
+    pub fn try_clone(&self) -> io::Result<Receiver> {
+        self.inner.try_clone().map(|inner| Receiver { inner })
+    }
+
+    /// Shut down the receiving half of the socket.
+    ///
+    /// Writing to the [`Sender`] returns a [`BrokenPipe`] error afterwards,
+    /// same as if the `Receiver` was dropped.
+    ///
+    /// [`BrokenPipe`]: io::ErrorKind::BrokenPipe
+    pub fn shutdown(&self) -> io::Result<()> {
+        shutdown(self.inner.as_raw_fd(), libc::SHUT_RD)
+    }
+
+    /// Returns the size of the next message, without removing it.
+    ///
+    /// Returns zero if the `Sender` was closed or shut down, or a
+    /// `WouldBlock` error if no message is available in non-blocking mode.
+    pub fn next_message_len(&self) -> io::Result<usize> {
+        let flags = libc::MSG_PEEK | libc::MSG_TRUNC;
+        let n = unsafe { libc::recv(self.inner.as_raw_fd(), std::ptr::null_mut(), 0, flags) };
+        if n == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
+}
+
+impl Read for Receiver {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        (&*self).read_vectored(bufs)
+    }
+}
+
+/// Every read receives a single message.
+///
+/// If the message doesn't fit in the provided buffer(s) the remainder is
+/// discarded and an [`InvalidData`] error is returned, use
+/// [`Receiver::next_message_len`] to determine the required buffer size.
+///
+/// [`InvalidData`]: io::ErrorKind::InvalidData
+impl Read for &Receiver {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_vectored(&mut [IoSliceMut::new(buf)])
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = bufs.as_mut_ptr() as *mut libc::iovec;
+        msg.msg_iovlen = bufs.len() as _;
+        let n = unsafe { libc::recvmsg(self.inner.as_raw_fd(), &mut msg, 0) };
+        if n == -1 {
+            Err(io::Error::last_os_error())
+        } else if msg.msg_flags & libc::MSG_TRUNC != 0 {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message doesn't fit in buffer, message truncated",
+            ))
+        } else {
+            Ok(n as usize)
+        }
+    }
+}
+
+fn shutdown(fd: RawFd, how: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::shutdown(fd, how) } == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+macro_rules! impl_fd_traits {
+    ($ty: ident) => {
+        impl event::Source for $ty {
+            fn register(
+                &mut self,
+                registry: &Registry,
+                token: Token,
+                interests: Interest,
+            ) -> io::Result<()> {
+                SourceFd(&self.inner.as_raw_fd()).register(registry, token, interests)
+            }
+
+            fn reregister(
+                &mut self,
+                registry: &Registry,
+                token: Token,
+                interests: Interest,
+            ) -> io::Result<()> {
+                SourceFd(&self.inner.as_raw_fd()).reregister(registry, token, interests)
+            }
+
+            fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+                SourceFd(&self.inner.as_raw_fd()).deregister(registry)
+            }
+        }
+
+        impl FromRawFd for $ty {
+            unsafe fn from_raw_fd(fd: RawFd) -> $ty {
+                $ty {
+                    inner: File::from_raw_fd(fd),
+                }
+            }
+        }
+
+        impl AsRawFd for $ty {
+            fn as_raw_fd(&self) -> RawFd {
+                self.inner.as_raw_fd()
+            }
+        }
+
+        impl IntoRawFd for $ty {
+            fn into_raw_fd(self) -> RawFd {
+                self.inner.into_raw_fd()
+            }
+        }
+
+        impl AsFd for $ty {
+            fn as_fd(&self) -> BorrowedFd<'_> {
+                self.inner.as_fd()
+            }
+        }
+
+        /// # Notes
+        ///
+        /// The file descriptor is **not** set to non-blocking, nor is it
+        /// checked to be a `SOCK_SEQPACKET` socket.
+        impl From<OwnedFd> for $ty {
+            fn from(fd: OwnedFd) -> $ty {
+                $ty {
+                    inner: File::from(fd),
+                }
+            }
+        }
+
+        impl From<$ty> for OwnedFd {
+            fn from(end: $ty) -> OwnedFd {
+                end.inner.into()
+            }
+        }
+    };
+}
+
+impl_fd_traits!(Sender);
+impl_fd_traits!(Receiver);
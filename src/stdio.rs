@@ -0,0 +1,534 @@
+//! Non-blocking standard in, out and error of the current process.
+//!
+//! Setting `O_NONBLOCK` on the standard streams of the process is a common
+//! footgun: the streams are usually shared with other processes, such as the
+//! parent shell, and the flag is set on the shared open file description. So
+//! setting it for this process also sets it for all other processes sharing
+//! the stream, many of which don't expect it.
+//!
+//! The functions in this module avoid this where possible by reopening the
+//! stream using `/proc/self/fd`, which creates a new open file description
+//! that can be set to non-blocking without affecting other processes. If
+//! that's not possible the file descriptor is duplicated and set to
+//! non-blocking, the original flags are restored once the wrapper type is
+//! dropped.
+//!
+//! Only pipes, sockets and terminals are supported, as other file types (e.g.
+//! regular files) can't be used with [`Poll`].
+//!
+//! [`Poll`]: mio::Poll
+//!
+//! This module also supports capturing standard out and error of the process,
+//! see [`capture_stdout`] and [`redirect_stdout`].
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IoSlice, IoSliceMut, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::thread;
+
+use mio::{event, Interest, Registry, Token};
+
+use crate::{new_pipe, Receiver, Sender};
+
+/// Type of a standard stream.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FileType {
+    /// Pipe or named pipe (FIFO).
+    Pipe,
+    /// Unix or network socket.
+    Socket,
+    /// Terminal.
+    Tty,
+    /// Character device, other than a terminal, e.g. `/dev/null`.
+    CharDevice,
+    /// Regular file.
+    File,
+    /// Any other file type, e.g. a directory.
+    Other,
+}
+
+impl FileType {
+    /// Returns `true` if the file type can be used with [`Poll`].
+    ///
+    /// [`Poll`]: mio::Poll
+    pub fn is_pollable(self) -> bool {
+        matches!(self, FileType::Pipe | FileType::Socket | FileType::Tty)
+    }
+
+    /// Determine the type of `fd`.
+    fn of(fd: RawFd) -> io::Result<FileType> {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(match stat.st_mode & libc::S_IFMT {
+            libc::S_IFIFO => FileType::Pipe,
+            libc::S_IFSOCK => FileType::Socket,
+            libc::S_IFCHR if unsafe { libc::isatty(fd) } == 1 => FileType::Tty,
+            libc::S_IFCHR => FileType::CharDevice,
+            libc::S_IFREG => FileType::File,
+            _ => FileType::Other,
+        })
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileType::Pipe => "pipe",
+            FileType::Socket => "socket",
+            FileType::Tty => "terminal",
+            FileType::CharDevice => "character device",
+            FileType::File => "regular file",
+            FileType::Other => "unknown file type",
+        })
+    }
+}
+
+/// Error returned by [`stdin`], [`stdout`] and [`stderr`].
+#[derive(Debug)]
+pub enum StdioError {
+    /// The stream is of a type that can't be used with [`Poll`].
+    ///
+    /// [`Poll`]: mio::Poll
+    Unsupported(FileType),
+    /// I/O error.
+    Io(io::Error),
+}
+
+impl From<io::Error> for StdioError {
+    fn from(err: io::Error) -> StdioError {
+        StdioError::Io(err)
+    }
+}
+
+impl From<StdioError> for io::Error {
+    fn from(err: StdioError) -> io::Error {
+        match err {
+            StdioError::Unsupported(_) => io::Error::new(io::ErrorKind::InvalidInput, err),
+            StdioError::Io(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for StdioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StdioError::Unsupported(file_type) => {
+                write!(f, "can't use a {} as non-blocking stream", file_type)
+            }
+            StdioError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for StdioError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StdioError::Unsupported(_) => None,
+            StdioError::Io(err) => Some(err),
+        }
+    }
+}
+
+/// Open the standard stream `fd` in non-blocking mode.
+///
+/// Returns the file, its type and the original flags if they need to be
+/// restored.
+fn open(fd: RawFd, write: bool) -> Result<(File, FileType, Option<libc::c_int>), StdioError> {
+    let file_type = FileType::of(fd)?;
+    if !file_type.is_pollable() {
+        return Err(StdioError::Unsupported(file_type));
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    {
+        use std::fs::OpenOptions;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        // Doesn't work for sockets, for which we use the fallback below.
+        let res = OpenOptions::new()
+            .read(!write)
+            .write(write)
+            .custom_flags(libc::O_NONBLOCK | libc::O_NOCTTY)
+            .open(format!("/proc/self/fd/{}", fd));
+        if let Ok(file) = res {
+            return Ok((file, file_type, None));
+        }
+    }
+
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let new_fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 3) };
+    if new_fd == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    // Safety: just duplicated the file descriptor above.
+    let file = unsafe { File::from_raw_fd(new_fd) };
+    if flags & libc::O_NONBLOCK != 0 {
+        // Already non-blocking, nothing to restore.
+        return Ok((file, file_type, None));
+    }
+    if unsafe { libc::fcntl(new_fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok((file, file_type, Some(flags)))
+}
+
+/// Restore the `flags` of `fd`, if any.
+fn restore(fd: RawFd, flags: Option<libc::c_int>) {
+    if let Some(flags) = flags {
+        // Nothing we can do about errors at this point.
+        let _ = unsafe { libc::fcntl(fd, libc::F_SETFL, flags) };
+    }
+}
+
+/// Returns standard in of the process in non-blocking mode.
+///
+/// See the [module documentation] for more.
+///
+/// [module documentation]: crate::stdio
+pub fn stdin() -> Result<Stdin, StdioError> {
+    let (file, file_type, flags) = open(libc::STDIN_FILENO, false)?;
+    Ok(Stdin {
+        receiver: Receiver::from(OwnedFd::from(file)),
+        file_type,
+        flags,
+    })
+}
+
+/// Returns standard out of the process in non-blocking mode.
+///
+/// See the [module documentation] for more.
+///
+/// [module documentation]: crate::stdio
+pub fn stdout() -> Result<Stdout, StdioError> {
+    let (file, file_type, flags) = open(libc::STDOUT_FILENO, true)?;
+    Ok(Stdout {
+        sender: Sender::from(OwnedFd::from(file)),
+        file_type,
+        flags,
+    })
+}
+
+/// Returns standard error of the process in non-blocking mode.
+///
+/// See the [module documentation] for more.
+///
+/// [module documentation]: crate::stdio
+pub fn stderr() -> Result<Stderr, StdioError> {
+    let (file, file_type, flags) = open(libc::STDERR_FILENO, true)?;
+    Ok(Stderr {
+        sender: Sender::from(OwnedFd::from(file)),
+        file_type,
+        flags,
+    })
+}
+
+/// Non-blocking standard in, see [`stdin`].
+#[derive(Debug)]
+pub struct Stdin {
+    receiver: Receiver,
+    file_type: FileType,
+    /// Original flags to restore, if any.
+    flags: Option<libc::c_int>,
+}
+
+impl Stdin {
+    /// Returns the type of the stream.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+}
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.receiver.read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        self.receiver.read_vectored(bufs)
+    }
+}
+
+impl Drop for Stdin {
+    fn drop(&mut self) {
+        restore(self.receiver.as_raw_fd(), self.flags)
+    }
+}
+
+/// Non-blocking standard out, see [`stdout`].
+#[derive(Debug)]
+pub struct Stdout {
+    sender: Sender,
+    file_type: FileType,
+    /// Original flags to restore, if any.
+    flags: Option<libc::c_int>,
+}
+
+impl Stdout {
+    /// Returns the type of the stream.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+}
+
+impl Drop for Stdout {
+    fn drop(&mut self) {
+        restore(self.sender.as_raw_fd(), self.flags)
+    }
+}
+
+/// Non-blocking standard error, see [`stderr`].
+#[derive(Debug)]
+pub struct Stderr {
+    sender: Sender,
+    file_type: FileType,
+    /// Original flags to restore, if any.
+    flags: Option<libc::c_int>,
+}
+
+impl Stderr {
+    /// Returns the type of the stream.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+}
+
+impl Drop for Stderr {
+    fn drop(&mut self) {
+        restore(self.sender.as_raw_fd(), self.flags)
+    }
+}
+
+macro_rules! impl_source {
+    ($type: ty, $field: ident) => {
+        impl event::Source for $type {
+            fn register(
+                &mut self,
+                registry: &Registry,
+                token: Token,
+                interests: Interest,
+            ) -> io::Result<()> {
+                self.$field.register(registry, token, interests)
+            }
+
+            fn reregister(
+                &mut self,
+                registry: &Registry,
+                token: Token,
+                interests: Interest,
+            ) -> io::Result<()> {
+                self.$field.reregister(registry, token, interests)
+            }
+
+            fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+                self.$field.deregister(registry)
+            }
+        }
+
+        impl AsRawFd for $type {
+            fn as_raw_fd(&self) -> RawFd {
+                self.$field.as_raw_fd()
+            }
+        }
+    };
+}
+
+impl_source!(Stdin, receiver);
+impl_source!(Stdout, sender);
+impl_source!(Stderr, sender);
+
+macro_rules! impl_write {
+    ($type: ty) => {
+        impl Write for $type {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.sender.write(buf)
+            }
+
+            fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+                self.sender.write_vectored(bufs)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.sender.flush()
+            }
+        }
+    };
+}
+
+impl_write!(Stdout);
+impl_write!(Stderr);
+
+/// Capture of standard out or error, see [`capture_stdout`].
+///
+/// Dropping the `Capture` restores the stream and discards the captured
+/// output, use [`Capture::finish`] to retrieve it.
+#[derive(Debug)]
+pub struct Capture {
+    redirect: Option<Redirect>,
+    thread: Option<thread::JoinHandle<io::Result<Vec<u8>>>>,
+}
+
+impl Capture {
+    /// Start capturing the stream `fd`.
+    fn start(fd: RawFd) -> io::Result<Capture> {
+        let (redirect, mut receiver) = Redirect::start(fd)?;
+        receiver.set_nonblocking(false)?;
+        let thread = thread::Builder::new()
+            .name("mio_pipe::stdio::Capture".to_owned())
+            .spawn(move || {
+                let mut output = Vec::new();
+                receiver.read_to_end(&mut output).map(|_| output)
+            })?;
+        Ok(Capture {
+            redirect: Some(redirect),
+            thread: Some(thread),
+        })
+    }
+
+    /// Restore the stream and return the captured output.
+    ///
+    /// # Notes
+    ///
+    /// This waits until all copies of the write end of the pipe are closed,
+    /// i.e. until all child processes spawned during the capture that
+    /// inherited the stream exited.
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        self.join()
+    }
+
+    fn join(&mut self) -> io::Result<Vec<u8>> {
+        // Restoring the stream closes our copy of the write end, after which
+        // the thread reads end of file.
+        drop(self.redirect.take());
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("capture thread panicked"))),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = self.join();
+    }
+}
+
+/// Capture all output written to standard out (file descriptor 1).
+///
+/// This replaces file descriptor 1 with the write end of a pipe (see
+/// [`new_pipe`]) in blocking mode, the receiving end is read on a helper
+/// thread. This captures output written directly to the file descriptor, e.g.
+/// by C libraries or child processes, which the output capturing of the Rust
+/// test harness doesn't see. Once the [`Capture`] is finished or dropped the
+/// original stream is restored.
+///
+/// Note that file descriptors are shared by all threads in the process, so
+/// this also captures the output of other threads. Rust's [`io::stdout`] is
+/// flushed before the stream is replaced and before it's restored.
+///
+/// [`new_pipe`]: crate::new_pipe
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+///
+/// use mio_pipe::stdio::capture_stdout;
+///
+/// # fn main() -> io::Result<()> {
+/// let capture = capture_stdout()?;
+/// // Output written directly to file descriptor 1.
+/// unsafe { libc::write(1, b"hello".as_ptr().cast(), 5) };
+/// assert_eq!(capture.finish()?, b"hello");
+/// # Ok(())
+/// # }
+/// ```
+pub fn capture_stdout() -> io::Result<Capture> {
+    Capture::start(libc::STDOUT_FILENO)
+}
+
+/// Capture all output written to standard error (file descriptor 2).
+///
+/// See [`capture_stdout`], Rust's [`io::stderr`] is unbuffered.
+pub fn capture_stderr() -> io::Result<Capture> {
+    Capture::start(libc::STDERR_FILENO)
+}
+
+/// Redirection of standard out or error, see [`redirect_stdout`].
+///
+/// Dropping the `Redirect` restores the original stream.
+#[derive(Debug)]
+pub struct Redirect {
+    /// Redirected file descriptor, 1 or 2.
+    fd: RawFd,
+    /// Copy of the original stream.
+    original: File,
+}
+
+impl Redirect {
+    /// Redirect the stream `fd` to a new pipe.
+    fn start(fd: RawFd) -> io::Result<(Redirect, Receiver)> {
+        let (sender, receiver) = new_pipe()?;
+        // Writers of the stream don't expect `WouldBlock` errors.
+        sender.set_nonblocking(false)?;
+
+        flush(fd);
+        let original = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 3) };
+        if original == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // Safety: just duplicated the file descriptor above.
+        let original = unsafe { File::from_raw_fd(original) };
+        if unsafe { libc::dup2(sender.as_raw_fd(), fd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((Redirect { fd, original }, receiver))
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        flush(self.fd);
+        // Nothing we can do about errors at this point.
+        let _ = unsafe { libc::dup2(self.original.as_raw_fd(), self.fd) };
+    }
+}
+
+/// Flush Rust's buffered standard out if `fd` is standard out.
+fn flush(fd: RawFd) {
+    if fd == libc::STDOUT_FILENO {
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Redirect standard out (file descriptor 1) to a new pipe, returning the
+/// receiving end.
+///
+/// Same as [`capture_stdout`], but the caller is responsible for reading the
+/// output from the returned [`Receiver`], which can be registered with
+/// [`Poll`]. Note that writers of the stream block once the pipe is full, so
+/// the `Receiver` must be read from while the stream is redirected.
+///
+/// The `Receiver` reads end of file once the [`Redirect`] is dropped (and
+/// all other copies of the write end are closed).
+///
+/// [`Poll`]: mio::Poll
+pub fn redirect_stdout() -> io::Result<(Redirect, Receiver)> {
+    Redirect::start(libc::STDOUT_FILENO)
+}
+
+/// Redirect standard error (file descriptor 2) to a new pipe, returning the
+/// receiving end.
+///
+/// See [`redirect_stdout`].
+pub fn redirect_stderr() -> io::Result<(Redirect, Receiver)> {
+    Redirect::start(libc::STDERR_FILENO)
+}
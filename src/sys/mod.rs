@@ -4,6 +4,6 @@
 mod unix;
 
 #[cfg(unix)]
-pub use unix::{new_pipe, Receiver, Sender};
+pub use unix::{new_pipe, poll, set_nonblocking, wait_any, AccessMode, Flags, Receiver, Sender};
 
 // TODO: add Windows implementation.
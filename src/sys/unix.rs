@@ -1,6 +1,8 @@
 use std::fs::File;
 use std::io::{self, IoSlice, IoSliceMut, Read, Write};
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
 
 use mio::unix::SourceFd;
 use mio::{event, Interest, Registry, Token};
@@ -8,12 +10,121 @@ use mio::{event, Interest, Registry, Token};
 #[derive(Debug)]
 pub struct Sender {
     inner: File,
+    /// Emulate non-blocking I/O using `poll(2)`, see
+    /// `set_nonblocking_emulated`.
+    emulate_nonblocking: AtomicBool,
+    /// Maximum number of times to retry I/O on `EINTR`, `u32::MAX` meaning
+    /// no limit.
+    max_interrupt_retries: AtomicU32,
 }
 
 impl Sender {
+    fn new(inner: File) -> Sender {
+        Sender {
+            inner,
+            emulate_nonblocking: AtomicBool::new(false),
+            max_interrupt_retries: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn set_nonblocking_emulated(&self, emulate: bool) {
+        self.emulate_nonblocking.store(emulate, Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_nonblocking_emulated(&self) -> bool {
+        self.emulate_nonblocking.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_max_interrupt_retries(&self, retries: u32) {
+        self.max_interrupt_retries.store(retries, Ordering::Relaxed)
+    }
+
+    pub(crate) fn max_interrupt_retries(&self) -> u32 {
+        self.max_interrupt_retries.load(Ordering::Relaxed)
+    }
+
     pub(crate) fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         set_nonblocking(self.inner.as_raw_fd(), nonblocking)
     }
+
+    pub(crate) fn set_cloexec(&self, cloexec: bool) -> io::Result<()> {
+        set_cloexec(self.inner.as_raw_fd(), cloexec)
+    }
+
+    pub(crate) fn flags(&self) -> io::Result<Flags> {
+        flags(self.inner.as_raw_fd())
+    }
+
+    pub(crate) fn close(self) -> io::Result<()> {
+        close(self.inner.into_raw_fd())
+    }
+
+    pub(crate) fn wait_writable(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        wait_ready(self.inner.as_raw_fd(), libc::POLLOUT, timeout)
+    }
+
+    pub(crate) fn write_timeout(&self, buf: &[u8], timeout: Duration) -> io::Result<usize> {
+        let deadline = Instant::now() + timeout;
+        // In blocking mode writes of more than `PIPE_BUF` bytes can block even
+        // if the pipe is writable.
+        let buf = if self.flags()?.is_nonblocking() {
+            buf
+        } else {
+            &buf[..buf.len().min(libc::PIPE_BUF)]
+        };
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if !self.wait_writable(Some(timeout))? {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "write timed out"));
+            }
+            match (&*self).write(buf) {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                res => return res,
+            }
+        }
+    }
+
+    /// Create a `Sender` from an inherited file descriptor, checking that it's
+    /// the write end of a pipe and setting the correct flags.
+    pub(crate) fn from_inherited_fd(fd: RawFd) -> io::Result<Sender> {
+        check_pipe(fd, true)?;
+        set_cloexec(fd, true)?;
+        set_nonblocking(fd, true)?;
+        Ok(Sender::new(unsafe { File::from_raw_fd(fd) }))
+    }
+
+    /// Create a `Sender` from the write end of a pipe created by other means,
+    /// setting the correct flags.
+    pub(crate) fn from_pipe_fd(fd: OwnedFd) -> io::Result<Sender> {
+        set_cloexec(fd.as_raw_fd(), true)?;
+        set_nonblocking(fd.as_raw_fd(), true)?;
+        Ok(Sender::from(fd))
+    }
+
+    /// Create a `Sender` from `fd`, checking that it's the write end of a pipe
+    /// and setting the correct flags.
+    pub(crate) fn try_from_fd(fd: OwnedFd) -> io::Result<Sender> {
+        check_pipe(fd.as_raw_fd(), true)?;
+        Sender::from_pipe_fd(fd)
+    }
+
+    pub(crate) fn try_clone(&self) -> io::Result<Sender> {
+        self.inner.try_clone().map(|inner| Sender {
+            inner,
+            emulate_nonblocking: AtomicBool::new(self.is_nonblocking_emulated()),
+            max_interrupt_retries: AtomicU32::new(self.max_interrupt_retries()),
+        })
+    }
+
+    /// Shrink the pipe's buffer to the smallest size supported, where possible.
+    pub(crate) fn shrink_buffer(&self) {
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        unsafe {
+            // Not being able to shrink the buffer is not an error, it only
+            // means it takes longer to fill it.
+            let _ = libc::fcntl(self.inner.as_raw_fd(), libc::F_SETPIPE_SZ, 4096);
+        }
+    }
 }
 
 impl event::Source for Sender {
@@ -42,11 +153,11 @@ impl event::Source for Sender {
 
 impl Write for Sender {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner.write(buf)
+        (&*self).write(buf)
     }
 
     fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
-        self.inner.write_vectored(bufs)
+        (&*self).write_vectored(bufs)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -54,11 +165,47 @@ impl Write for Sender {
     }
 }
 
+impl Write for &Sender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let sender = *self;
+        retry_interrupted(sender.max_interrupt_retries(), || {
+            if sender.is_nonblocking_emulated() {
+                check_ready(sender.inner.as_raw_fd(), libc::POLLOUT)?;
+                // Writes of at most `PIPE_BUF` bytes don't block if the pipe
+                // is writable.
+                let len = buf.len().min(libc::PIPE_BUF);
+                (&sender.inner).write(&buf[..len])
+            } else {
+                (&sender.inner).write(buf)
+            }
+        })
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        if self.is_nonblocking_emulated() {
+            // Only write the first non-empty buffer to limit the write to
+            // `PIPE_BUF` bytes, see `write` above.
+            let buf = bufs
+                .iter()
+                .find(|buf| !buf.is_empty())
+                .map_or(&[][..], |buf| &**buf);
+            self.write(buf)
+        } else {
+            let sender = *self;
+            retry_interrupted(sender.max_interrupt_retries(), || {
+                (&sender.inner).write_vectored(bufs)
+            })
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.inner).flush()
+    }
+}
+
 impl FromRawFd for Sender {
     unsafe fn from_raw_fd(fd: RawFd) -> Sender {
-        Sender {
-            inner: File::from_raw_fd(fd),
-        }
+        Sender::new(File::from_raw_fd(fd))
     }
 }
 
@@ -74,15 +221,125 @@ impl IntoRawFd for Sender {
     }
 }
 
+impl AsFd for Sender {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl From<OwnedFd> for Sender {
+    fn from(fd: OwnedFd) -> Sender {
+        Sender::new(File::from(fd))
+    }
+}
+
+impl From<Sender> for OwnedFd {
+    fn from(end: Sender) -> OwnedFd {
+        end.inner.into()
+    }
+}
+
 #[derive(Debug)]
 pub struct Receiver {
     inner: File,
+    /// Emulate non-blocking I/O using `poll(2)`, see
+    /// `set_nonblocking_emulated`.
+    emulate_nonblocking: AtomicBool,
+    /// Maximum number of times to retry I/O on `EINTR`, `u32::MAX` meaning
+    /// no limit.
+    max_interrupt_retries: AtomicU32,
 }
 
 impl Receiver {
+    fn new(inner: File) -> Receiver {
+        Receiver {
+            inner,
+            emulate_nonblocking: AtomicBool::new(false),
+            max_interrupt_retries: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn set_nonblocking_emulated(&self, emulate: bool) {
+        self.emulate_nonblocking.store(emulate, Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_nonblocking_emulated(&self) -> bool {
+        self.emulate_nonblocking.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_max_interrupt_retries(&self, retries: u32) {
+        self.max_interrupt_retries.store(retries, Ordering::Relaxed)
+    }
+
+    pub(crate) fn max_interrupt_retries(&self) -> u32 {
+        self.max_interrupt_retries.load(Ordering::Relaxed)
+    }
+
     pub(crate) fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         set_nonblocking(self.inner.as_raw_fd(), nonblocking)
     }
+
+    pub(crate) fn set_cloexec(&self, cloexec: bool) -> io::Result<()> {
+        set_cloexec(self.inner.as_raw_fd(), cloexec)
+    }
+
+    pub(crate) fn flags(&self) -> io::Result<Flags> {
+        flags(self.inner.as_raw_fd())
+    }
+
+    pub(crate) fn close(self) -> io::Result<()> {
+        close(self.inner.into_raw_fd())
+    }
+
+    pub(crate) fn wait_readable(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        wait_ready(self.inner.as_raw_fd(), libc::POLLIN, timeout)
+    }
+
+    pub(crate) fn read_timeout(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if !self.wait_readable(Some(timeout))? {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "read timed out"));
+            }
+            match (&*self).read(buf) {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                res => return res,
+            }
+        }
+    }
+
+    /// Create a `Receiver` from an inherited file descriptor, checking that
+    /// it's the read end of a pipe and setting the correct flags.
+    pub(crate) fn from_inherited_fd(fd: RawFd) -> io::Result<Receiver> {
+        check_pipe(fd, false)?;
+        set_cloexec(fd, true)?;
+        set_nonblocking(fd, true)?;
+        Ok(Receiver::new(unsafe { File::from_raw_fd(fd) }))
+    }
+
+    /// Create a `Receiver` from the read end of a pipe created by other means,
+    /// setting the correct flags.
+    pub(crate) fn from_pipe_fd(fd: OwnedFd) -> io::Result<Receiver> {
+        set_cloexec(fd.as_raw_fd(), true)?;
+        set_nonblocking(fd.as_raw_fd(), true)?;
+        Ok(Receiver::from(fd))
+    }
+
+    /// Create a `Receiver` from `fd`, checking that it's the read end of a pipe
+    /// and setting the correct flags.
+    pub(crate) fn try_from_fd(fd: OwnedFd) -> io::Result<Receiver> {
+        check_pipe(fd.as_raw_fd(), false)?;
+        Receiver::from_pipe_fd(fd)
+    }
+
+    pub(crate) fn try_clone(&self) -> io::Result<Receiver> {
+        self.inner.try_clone().map(|inner| Receiver {
+            inner,
+            emulate_nonblocking: AtomicBool::new(self.is_nonblocking_emulated()),
+            max_interrupt_retries: AtomicU32::new(self.max_interrupt_retries()),
+        })
+    }
 }
 
 impl event::Source for Receiver {
@@ -111,19 +368,39 @@ impl event::Source for Receiver {
 
 impl Read for Receiver {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        (&*self).read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+        (&*self).read_vectored(bufs)
+    }
+}
+
+impl Read for &Receiver {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let receiver = *self;
+        retry_interrupted(receiver.max_interrupt_retries(), || {
+            if receiver.is_nonblocking_emulated() {
+                check_ready(receiver.inner.as_raw_fd(), libc::POLLIN)?;
+            }
+            (&receiver.inner).read(buf)
+        })
     }
 
     fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
-        self.inner.read_vectored(bufs)
+        let receiver = *self;
+        retry_interrupted(receiver.max_interrupt_retries(), || {
+            if receiver.is_nonblocking_emulated() {
+                check_ready(receiver.inner.as_raw_fd(), libc::POLLIN)?;
+            }
+            (&receiver.inner).read_vectored(bufs)
+        })
     }
 }
 
 impl FromRawFd for Receiver {
     unsafe fn from_raw_fd(fd: RawFd) -> Receiver {
-        Receiver {
-            inner: File::from_raw_fd(fd),
-        }
+        Receiver::new(File::from_raw_fd(fd))
     }
 }
 
@@ -139,15 +416,265 @@ impl IntoRawFd for Receiver {
     }
 }
 
-fn set_nonblocking(fd: RawFd, nonblocking: bool) -> io::Result<()> {
+impl AsFd for Receiver {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+impl From<OwnedFd> for Receiver {
+    fn from(fd: OwnedFd) -> Receiver {
+        Receiver::new(File::from(fd))
+    }
+}
+
+impl From<Receiver> for OwnedFd {
+    fn from(end: Receiver) -> OwnedFd {
+        end.inner.into()
+    }
+}
+
+pub fn set_nonblocking(fd: RawFd, nonblocking: bool) -> io::Result<()> {
     let value = nonblocking as libc::c_int;
     if unsafe { libc::ioctl(fd, libc::FIONBIO, &value) } == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Call `f`, retrying it at most `max_retries` times (`u32::MAX` meaning no
+/// limit) if it returns an `Interrupted` error.
+fn retry_interrupted<T, F>(max_retries: u32, mut f: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut retries = 0;
+    loop {
+        match f() {
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted && retries < max_retries => {
+                if max_retries != u32::MAX {
+                    retries += 1;
+                }
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Check if `fd` is ready for `events` (`POLLIN` or `POLLOUT`) without
+/// blocking, returning a `WouldBlock` error if it's not.
+fn check_ready(fd: RawFd, events: libc::c_short) -> io::Result<()> {
+    if wait_ready(fd, events, Some(Duration::from_secs(0)))? {
+        Ok(())
+    } else {
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+/// Wait until `fd` is ready for `events` (`POLLIN` or `POLLOUT`), returns
+/// `false` if the `timeout` elapsed.
+///
+/// Errors and hang ups are considered ready, so that the following read or
+/// write returns them.
+fn wait_ready(fd: RawFd, events: libc::c_short, timeout: Option<Duration>) -> io::Result<bool> {
+    let mut fds = [libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    }];
+    poll(&mut fds, timeout).map(|n| n != 0)
+}
+
+/// Wait until any of `fds` is ready, using the access mode of the file
+/// descriptor to determine whether to wait for reading or writing.
+///
+/// Returns the indices of the ready file descriptors, or an empty vector if
+/// the `timeout` elapsed.
+pub fn wait_any(fds: &[RawFd], timeout: Option<Duration>) -> io::Result<Vec<usize>> {
+    let mut pollfds = Vec::with_capacity(fds.len());
+    for fd in fds.iter().copied() {
+        let events = match flags(fd)?.access_mode() {
+            AccessMode::Read => libc::POLLIN,
+            AccessMode::Write => libc::POLLOUT,
+            AccessMode::ReadWrite => libc::POLLIN | libc::POLLOUT,
+        };
+        pollfds.push(libc::pollfd {
+            fd,
+            events,
+            revents: 0,
+        });
+    }
+    poll(&mut pollfds, timeout)?;
+    Ok(pollfds
+        .iter()
+        .enumerate()
+        .filter(|(_, pollfd)| pollfd.revents != 0)
+        .map(|(i, _)| i)
+        .collect())
+}
+
+/// Wrapper around [`poll(2)`], retrying on interrupts.
+///
+/// Returns the number of file descriptors with events, or zero if the timeout
+/// elapsed.
+///
+/// [`poll(2)`]: https://pubs.opengroup.org/onlinepubs/9699919799/functions/poll.html
+pub fn poll(fds: &mut [libc::pollfd], timeout: Option<Duration>) -> io::Result<usize> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        let timeout = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                // Round up to not return before the deadline.
+                let millis = left.as_nanos().div_ceil(1_000_000);
+                millis.min(libc::c_int::MAX as u128) as libc::c_int
+            }
+            None => -1,
+        };
+        match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            n => return Ok(n as usize),
+        }
+    }
+}
+
+fn set_cloexec(fd: RawFd, cloexec: bool) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 {
         return Err(io::Error::last_os_error());
+    }
+    let flags = if cloexec {
+        flags | libc::FD_CLOEXEC
+    } else {
+        flags & !libc::FD_CLOEXEC
+    };
+    if unsafe { libc::fcntl(fd, libc::F_SETFD, flags) } == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Access mode of a pipe end, see [`Flags::access_mode`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessMode {
+    /// Opened for reading only.
+    Read,
+    /// Opened for writing only.
+    Write,
+    /// Opened for both reading and writing, e.g. a named pipe opened using
+    /// `O_RDWR`.
+    ReadWrite,
+}
+
+/// File descriptor flags of a pipe end, returned by [`Sender::flags`] and
+/// [`Receiver::flags`].
+///
+/// This is a view of the flags returned by `fcntl(2)` with `F_GETFL` and
+/// `F_GETFD`.
+///
+/// [`Sender::flags`]: crate::Sender::flags
+/// [`Receiver::flags`]: crate::Receiver::flags
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Flags {
+    access_mode: AccessMode,
+    nonblocking: bool,
+    cloexec: bool,
+}
+
+impl Flags {
+    /// Returns the access mode (`O_ACCMODE`).
+    pub fn access_mode(&self) -> AccessMode {
+        self.access_mode
+    }
+
+    /// Returns `true` if `O_NONBLOCK` is set.
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking
+    }
+
+    /// Returns `true` if `FD_CLOEXEC` is set.
+    pub fn is_cloexec(&self) -> bool {
+        self.cloexec
+    }
+}
+
+/// Close `fd`, returning any error.
+///
+/// This doesn't retry on `EINTR`, as the file descriptor is already closed on
+/// most platforms (including Linux) and retrying could close a file descriptor
+/// opened by another thread in the meantime.
+fn close(fd: RawFd) -> io::Result<()> {
+    if unsafe { libc::close(fd) } == -1 {
+        Err(io::Error::last_os_error())
     } else {
         Ok(())
     }
 }
 
+fn flags(fd: RawFd) -> io::Result<Flags> {
+    let status = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if status == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let access_mode = match status & libc::O_ACCMODE {
+        libc::O_RDONLY => AccessMode::Read,
+        libc::O_WRONLY => AccessMode::Write,
+        _ => AccessMode::ReadWrite,
+    };
+    Ok(Flags {
+        access_mode,
+        nonblocking: status & libc::O_NONBLOCK != 0,
+        cloexec: flags & libc::FD_CLOEXEC != 0,
+    })
+}
+
+/// Check that `fd` is a pipe (or named pipe) opened for writing if `write` is
+/// true, or reading otherwise.
+fn check_pipe(fd: RawFd, write: bool) -> io::Result<()> {
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let msg = match stat.st_mode & libc::S_IFMT {
+        libc::S_IFIFO => None,
+        libc::S_IFSOCK => Some("file descriptor is a socket, not a pipe"),
+        libc::S_IFREG => Some("file descriptor is a regular file, not a pipe"),
+        libc::S_IFCHR => Some("file descriptor is a character device, not a pipe"),
+        libc::S_IFDIR => Some("file descriptor is a directory, not a pipe"),
+        _ => Some("file descriptor is not a pipe"),
+    };
+    if let Some(msg) = msg {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+    }
+
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    match (flags & libc::O_ACCMODE, write) {
+        (libc::O_RDWR, _) | (libc::O_WRONLY, true) | (libc::O_RDONLY, false) => Ok(()),
+        (_, true) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "file descriptor is not the write end of a pipe",
+        )),
+        (_, false) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "file descriptor is not the read end of a pipe",
+        )),
+    }
+}
+
 pub fn new_pipe() -> io::Result<(Sender, Receiver)> {
     let mut fds: [RawFd; 2] = [-1, -1];
 
@@ -186,11 +713,7 @@ pub fn new_pipe() -> io::Result<(Sender, Receiver)> {
         }
     }
 
-    let r = Receiver {
-        inner: unsafe { File::from_raw_fd(fds[0]) },
-    };
-    let w = Sender {
-        inner: unsafe { File::from_raw_fd(fds[1]) },
-    };
+    let r = Receiver::new(unsafe { File::from_raw_fd(fds[0]) });
+    let w = Sender::new(unsafe { File::from_raw_fd(fds[1]) });
     Ok((w, r))
 }
@@ -0,0 +1,200 @@
+//! Cross-thread waker with wake reasons.
+//!
+//! See [`PipeWaker`].
+
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use mio::{event, Interest, Registry, Token};
+
+use crate::{new_pipe, Receiver, Sender};
+
+/// Waker backed by a Unix pipe.
+///
+/// Similar to [`mio::Waker`], but unlike it multiple `PipeWaker`s can be
+/// registered with the same [`Poll`] instance, e.g. one per subsystem each
+/// with their own token. Furthermore when waking a reason can be provided, see
+/// [`wake_with_reason`], which can be retrieved using [`drain`].
+///
+/// The `PipeWaker` needs to be registered with [`READABLE`] interest, after
+/// which it can be shared between threads (e.g. using an `Arc`). Waking the
+/// `PipeWaker` multiple times before it's drained will only generate a single
+/// event.
+///
+/// [`Poll`]: mio::Poll
+/// [`wake_with_reason`]: PipeWaker::wake_with_reason
+/// [`drain`]: PipeWaker::drain
+/// [`READABLE`]: Interest::READABLE
+///
+/// # Notes
+///
+/// After receiving an event [`drain`] must be called, otherwise no more events
+/// will be generated.
+///
+/// It's possible to receive an event and get an empty set of reasons from
+/// [`drain`], this happens if a wake up raced with a previous call to `drain`.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// use mio::{Events, Interest, Poll, Token};
+/// use mio_pipe::waker::PipeWaker;
+///
+/// const WAKER: Token = Token(0);
+/// const NEW_WORK: u8 = 1;
+///
+/// # fn main() -> io::Result<()> {
+/// let mut poll = Poll::new()?;
+/// let mut events = Events::with_capacity(8);
+///
+/// let mut waker = PipeWaker::new()?;
+/// poll.registry().register(&mut waker, WAKER, Interest::READABLE)?;
+///
+/// let waker = Arc::new(waker);
+/// let thread_waker = waker.clone();
+/// let handle = thread::spawn(move || thread_waker.wake_with_reason(NEW_WORK));
+///
+/// poll.poll(&mut events, None)?;
+/// for event in events.iter() {
+///     assert_eq!(event.token(), WAKER);
+///     let reasons = waker.drain()?;
+///     assert!(reasons.contains(NEW_WORK));
+/// }
+/// # handle.join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PipeWaker {
+    sender: Sender,
+    receiver: Receiver,
+    /// True if a byte was written into the pipe that isn't drained yet.
+    pending: AtomicBool,
+    /// Bitmap of the reasons passed to `wake_with_reason`.
+    reasons: AtomicU64,
+}
+
+/// Maximum reason that can be passed to [`PipeWaker::wake_with_reason`].
+pub const MAX_REASON: u8 = 63;
+
+impl PipeWaker {
+    /// Create a new `PipeWaker`.
+    pub fn new() -> io::Result<PipeWaker> {
+        let (sender, receiver) = new_pipe()?;
+        Ok(PipeWaker {
+            sender,
+            receiver,
+            pending: AtomicBool::new(false),
+            reasons: AtomicU64::new(0),
+        })
+    }
+
+    /// Wake up the `PipeWaker` without a reason.
+    pub fn wake(&self) -> io::Result<()> {
+        if self.pending.swap(true, Ordering::AcqRel) {
+            // Already woken, but not yet drained.
+            return Ok(());
+        }
+
+        match (&self.sender).write(&[1]) {
+            Ok(_) => Ok(()),
+            // If the pipe is full an event will be generated anyway.
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Wake up the `PipeWaker` with `reason`.
+    ///
+    /// The `reason` must be at most [`MAX_REASON`], otherwise this returns an
+    /// error with kind `InvalidInput`.
+    pub fn wake_with_reason(&self, reason: u8) -> io::Result<()> {
+        if reason > MAX_REASON {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "wake reason larger than `MAX_REASON`",
+            ));
+        }
+
+        // NOTE: the reason must be set before `pending` is checked in `wake`,
+        // so that `drain` always sees it.
+        let _ = self.reasons.fetch_or(1 << reason, Ordering::AcqRel);
+        self.wake()
+    }
+
+    /// Drain the `PipeWaker`, returning all reasons it was woken with since the
+    /// last call to `drain`.
+    ///
+    /// This must be called after receiving an event for the `PipeWaker`.
+    pub fn drain(&self) -> io::Result<WakeReasons> {
+        let mut buf = [0; 64];
+        loop {
+            match (&self.receiver).read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => continue,
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        // NOTE: `pending` must only be reset after the pipe is empty. If it's
+        // reset before reading, a concurrent `wake` could write a byte that is
+        // read below, leaving `pending` set with an empty pipe, after which no
+        // more events are generated. The reasons must be taken after resetting
+        // `pending`, so that a reason set by a `wake_with_reason` call that
+        // didn't write to the pipe is still returned.
+        self.pending.store(false, Ordering::Release);
+        Ok(WakeReasons(self.reasons.swap(0, Ordering::AcqRel)))
+    }
+}
+
+impl event::Source for PipeWaker {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.receiver.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        self.receiver.deregister(registry)
+    }
+}
+
+/// Set of reasons a [`PipeWaker`] was woken with, returned by
+/// [`PipeWaker::drain`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct WakeReasons(u64);
+
+impl WakeReasons {
+    /// Returns `true` if no reasons were provided.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if the `PipeWaker` was woken with `reason`.
+    pub const fn contains(self, reason: u8) -> bool {
+        reason <= MAX_REASON && self.0 & (1 << reason) != 0
+    }
+
+    /// Returns an iterator over all reasons, in ascending order.
+    pub fn iter(self) -> impl Iterator<Item = u8> {
+        (0..=MAX_REASON).filter(move |reason| self.contains(*reason))
+    }
+}
@@ -0,0 +1,92 @@
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::Command;
+use std::time::Duration;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::cancel::CancelSource;
+use mio_pipe::process::CommandExt;
+
+const TOKEN1: Token = Token(0);
+const TOKEN2: Token = Token(1);
+
+#[test]
+fn cancel_wakes_all_tokens() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let source = CancelSource::new().unwrap();
+    let mut token1 = source.token().unwrap();
+    let mut token2 = token1.try_clone().unwrap();
+    poll.registry()
+        .register(&mut token1, TOKEN1, Interest::READABLE)
+        .unwrap();
+    poll.registry()
+        .register(&mut token2, TOKEN2, Interest::READABLE)
+        .unwrap();
+
+    assert!(!token1.is_cancelled().unwrap());
+    assert!(!token2.wait(Some(Duration::from_millis(10))).unwrap());
+
+    source.cancel();
+    let mut cancelled = Vec::new();
+    while cancelled.len() < 2 {
+        poll.poll(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        assert!(!events.is_empty(), "tokens not cancelled");
+        for event in events.iter() {
+            assert!(event.is_read_closed());
+            cancelled.push(event.token());
+        }
+    }
+    cancelled.sort();
+    assert_eq!(cancelled, [TOKEN1, TOKEN2]);
+    assert!(token1.is_cancelled().unwrap());
+    assert!(token2.wait(None).unwrap());
+}
+
+#[test]
+fn cancel_child_process() {
+    let source = CancelSource::new().unwrap();
+    let token = source.token().unwrap();
+
+    // The child blocks until the token is cancelled.
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "cat <&3"])
+        .pass_fd(3, token.into_inheritable().unwrap());
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(child.try_wait().unwrap().is_none());
+
+    drop(source);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn into_inheritable_keeps_other_tokens_non_blocking() {
+    let source = CancelSource::new().unwrap();
+    let token1 = source.token().unwrap();
+    let token2 = token1.try_clone().unwrap();
+
+    let inheritable = token1.into_inheritable().unwrap();
+    assert_eq!(get_fl(inheritable.as_raw_fd()) & libc::O_NONBLOCK, 0);
+    assert_ne!(get_fl(token2.as_raw_fd()) & libc::O_NONBLOCK, 0);
+
+    // Reopening also works once cancelled.
+    let token3 = token2.try_clone().unwrap();
+    drop(source);
+    let inheritable = token3.into_inheritable().unwrap();
+    assert_eq!(get_fl(inheritable.as_raw_fd()) & libc::O_NONBLOCK, 0);
+    assert!(token2.is_cancelled().unwrap());
+}
+
+/// Returns the file status flags of `fd`.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+fn get_fl(fd: RawFd) -> libc::c_int {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    assert_ne!(flags, -1);
+    flags
+}
@@ -0,0 +1,70 @@
+use std::io::{self, Read, Write};
+use std::process::Command;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::stdio::{capture_stderr, capture_stdout, redirect_stdout};
+
+mod util;
+
+use util::{expect_events, expect_one_closed_event, ExpectEvent};
+
+/// Write `data` directly to `fd`, bypassing any Rust buffering.
+fn write_fd(fd: i32, data: &[u8]) {
+    let n = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+    assert_eq!(n, data.len() as isize);
+}
+
+// NOTE: standard out is shared by all tests, so all tests using it are run
+// sequentially in this test. The test harness itself can also write to
+// standard out, so we only check that the output contains what we wrote.
+#[test]
+fn stdout() {
+    // Capture.
+    let capture = capture_stdout().unwrap();
+    write_fd(1, b"#fd#");
+    io::stdout().write_all(b"#rust#").unwrap();
+    let status = Command::new("sh")
+        .args(["-c", "printf '#child#'"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = String::from_utf8(capture.finish().unwrap()).unwrap();
+    assert!(output.contains("#fd#"), "output: {}", output);
+    assert!(output.contains("#rust#"), "output: {}", output);
+    assert!(output.contains("#child#"), "output: {}", output);
+
+    // Dropping discards the output.
+    let capture = capture_stdout().unwrap();
+    write_fd(1, b"#discarded#");
+    drop(capture);
+
+    // Redirect.
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+    let (redirect, mut receiver) = redirect_stdout().unwrap();
+    poll.registry()
+        .register(&mut receiver, Token(0), Interest::READABLE)
+        .unwrap();
+    write_fd(1, b"#redirect#");
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(Token(0), Interest::READABLE)],
+    );
+    let mut buf = [0; 64];
+    let n = receiver.read(&mut buf).unwrap();
+    let output = String::from_utf8_lossy(&buf[..n]);
+    assert!(output.contains("#redirect#"), "output: {}", output);
+
+    drop(redirect);
+    expect_one_closed_event(&mut poll, &mut events, Token(0), true);
+}
+
+#[test]
+fn stderr() {
+    let capture = capture_stderr().unwrap();
+    write_fd(2, b"#fd#");
+    let output = String::from_utf8(capture.finish().unwrap()).unwrap();
+    assert!(output.contains("#fd#"), "output: {}", output);
+}
@@ -0,0 +1,118 @@
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::channel::{self, TryRecvError, TrySendError};
+
+mod util;
+
+use util::{expect_events, expect_one_closed_event, ExpectEvent};
+
+const RECEIVER: Token = Token(0);
+const SENDER: Token = Token(1);
+
+#[test]
+fn smoke() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (sender, mut receiver) = channel::bounded(2).unwrap();
+    poll.registry()
+        .register(&mut receiver, RECEIVER, Interest::READABLE)
+        .unwrap();
+
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+    assert!(receiver.is_empty());
+
+    sender.try_send(1).unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(RECEIVER, Interest::READABLE)],
+    );
+    assert_eq!(receiver.len(), 1);
+    assert_eq!(receiver.try_recv(), Ok(1));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+}
+
+#[test]
+fn writable_event_when_space_is_available() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut sender, mut receiver) = channel::bounded(2).unwrap();
+    poll.registry()
+        .register(&mut sender, SENDER, Interest::WRITABLE)
+        .unwrap();
+    assert_eq!(sender.capacity(), 2);
+
+    sender.try_send(1).unwrap();
+    sender.try_send(2).unwrap();
+    match sender.try_send(3) {
+        Err(TrySendError::Full(3)) => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+
+    let barrier = Arc::new(Barrier::new(2));
+    let thread_barrier = barrier.clone();
+    let handle = thread::spawn(move || {
+        assert_eq!(receiver.try_recv(), Ok(1));
+        thread_barrier.wait();
+        receiver
+    });
+
+    barrier.wait();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(SENDER, Interest::WRITABLE)],
+    );
+    sender.try_send(3).unwrap();
+
+    let mut receiver = handle.join().unwrap();
+    assert_eq!(receiver.try_recv(), Ok(2));
+    assert_eq!(receiver.try_recv(), Ok(3));
+}
+
+#[test]
+fn event_when_all_senders_are_dropped() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (sender1, mut receiver) = channel::bounded(4).unwrap();
+    let sender2 = sender1.clone();
+    poll.registry()
+        .register(&mut receiver, RECEIVER, Interest::READABLE)
+        .unwrap();
+
+    sender2.try_send("a").unwrap();
+    drop(sender1);
+    drop(sender2);
+
+    expect_one_closed_event(&mut poll, &mut events, RECEIVER, true);
+    // Values send before are still received.
+    assert_eq!(receiver.try_recv(), Ok("a"));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+}
+
+#[test]
+fn event_when_receiver_is_dropped() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut sender, receiver) = channel::bounded(1).unwrap();
+    sender.try_send(1).unwrap();
+    assert!(matches!(sender.try_send(2), Err(TrySendError::Full(2))));
+    poll.registry()
+        .register(&mut sender, SENDER, Interest::WRITABLE)
+        .unwrap();
+
+    drop(receiver);
+
+    expect_one_closed_event(&mut poll, &mut events, SENDER, false);
+    assert!(matches!(
+        sender.try_send(2),
+        Err(TrySendError::Disconnected(2))
+    ));
+}
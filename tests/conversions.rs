@@ -0,0 +1,67 @@
+#![cfg(any(feature = "std-pipe", feature = "os_pipe"))]
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+
+use mio_pipe::{Receiver, Sender};
+
+mod util;
+
+use util::assert_would_block;
+
+/// Returns the `O_NONBLOCK` and `FD_CLOEXEC` flags of `fd`.
+fn flags<F: AsRawFd>(fd: &F) -> (bool, bool) {
+    let fd = fd.as_raw_fd();
+    let status = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    assert!(status != -1 && flags != -1);
+    (
+        status & libc::O_NONBLOCK != 0,
+        flags & libc::FD_CLOEXEC != 0,
+    )
+}
+
+#[cfg(feature = "std-pipe")]
+#[test]
+fn std_pipe() {
+    let (reader, writer) = std::io::pipe().unwrap();
+    let mut receiver = Receiver::try_from(reader).unwrap();
+    let mut sender = Sender::try_from(writer).unwrap();
+    assert_eq!(flags(&receiver), (true, true));
+    assert_eq!(flags(&sender), (true, true));
+
+    assert_would_block(receiver.read(&mut [0; 8]));
+    sender.write_all(b"hello").unwrap();
+    let mut buf = [0; 8];
+    assert_eq!(receiver.read(&mut buf).unwrap(), 5);
+
+    let mut reader = std::io::PipeReader::try_from(receiver).unwrap();
+    let writer = std::io::PipeWriter::try_from(sender).unwrap();
+    assert_eq!(flags(&reader), (false, true));
+    assert_eq!(flags(&writer), (false, true));
+    drop(writer);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+}
+
+#[cfg(feature = "os_pipe")]
+#[test]
+fn os_pipe() {
+    let (reader, writer) = os_pipe::pipe().unwrap();
+    let mut receiver = Receiver::try_from(reader).unwrap();
+    let mut sender = Sender::try_from(writer).unwrap();
+    assert_eq!(flags(&receiver), (true, true));
+    assert_eq!(flags(&sender), (true, true));
+
+    assert_would_block(receiver.read(&mut [0; 8]));
+    sender.write_all(b"hello").unwrap();
+    let mut buf = [0; 8];
+    assert_eq!(receiver.read(&mut buf).unwrap(), 5);
+
+    let mut reader = os_pipe::PipeReader::try_from(receiver).unwrap();
+    let writer = os_pipe::PipeWriter::try_from(sender).unwrap();
+    assert_eq!(flags(&reader), (false, true));
+    assert_eq!(flags(&writer), (false, true));
+    drop(writer);
+    assert_eq!(reader.read(&mut buf).unwrap(), 0);
+}
@@ -0,0 +1,61 @@
+use std::env;
+use std::process::{self, Command, Output};
+
+use mio_pipe::daemon::daemonize;
+
+/// Environment variable used to select the behaviour of the daemon in
+/// `daemon_process`.
+const MODE: &str = "MIO_PIPE_DAEMON_TEST";
+
+/// Not an actual test, run as a separate process by the tests below.
+#[test]
+fn daemon_process() {
+    let mode = match env::var(MODE) {
+        Ok(mode) => mode,
+        Err(_) => return,
+    };
+
+    let daemon = unsafe { daemonize() }.unwrap();
+    match mode.as_str() {
+        "ok" => daemon.report_ok().unwrap(),
+        "error" => daemon.report_error(3, "failed to initialise").unwrap(),
+        _ => drop(daemon),
+    }
+    process::exit(0);
+}
+
+fn run_daemon(mode: &str) -> Output {
+    Command::new(env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "daemon_process",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env(MODE, mode)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn report_ok() {
+    let output = run_daemon("ok");
+    assert!(output.status.success(), "output: {:?}", output);
+}
+
+#[test]
+fn report_error() {
+    let output = run_daemon("error");
+    assert_eq!(output.status.code(), Some(3), "output: {:?}", output);
+    assert_eq!(output.stderr, b"failed to initialise\n");
+}
+
+#[test]
+fn no_report() {
+    let output = run_daemon("drop");
+    assert_eq!(output.status.code(), Some(1), "output: {:?}", output);
+    assert_eq!(
+        output.stderr,
+        b"daemon exited without reporting its status\n"
+    );
+}
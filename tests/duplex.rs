@@ -0,0 +1,191 @@
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::duplex::{new_duplex, DuplexPipe};
+use mio_pipe::new_pipe;
+
+mod util;
+
+use util::{assert_would_block, expect_events, ExpectEvent};
+
+const PARENT: Token = Token(0);
+const CHILD: Token = Token(1);
+
+#[test]
+fn read_write_both_directions() {
+    let (mut parent, mut child) = new_duplex().unwrap();
+    let mut buf = [0; 8];
+    assert_would_block(parent.read(&mut buf));
+    assert_would_block(child.read(&mut buf));
+
+    parent.write_all(b"ping").unwrap();
+    assert_eq!(child.read(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], b"ping");
+    // Data isn't looped back.
+    assert_would_block(parent.read(&mut buf));
+
+    child.write_all(b"pong").unwrap();
+    assert_eq!(parent.read(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], b"pong");
+}
+
+#[test]
+fn close_write() {
+    let (mut parent, mut child) = new_duplex().unwrap();
+    assert!(!parent.is_write_closed());
+
+    parent.write_all(b"hello").unwrap();
+    parent.close_write();
+    assert!(parent.is_write_closed());
+
+    let mut output = Vec::new();
+    child.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"hello");
+
+    let err = parent.write(b"hello").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+    // The other direction still works.
+    child.write_all(b"world").unwrap();
+    let mut buf = [0; 8];
+    assert_eq!(parent.read(&mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"world");
+}
+
+#[test]
+fn split() {
+    let (parent, child) = new_duplex().unwrap();
+    parent.set_nonblocking(false).unwrap();
+    child.set_nonblocking(false).unwrap();
+
+    // Echo everything back.
+    let handle = thread::spawn(move || {
+        let (mut receiver, sender) = child.split();
+        let mut sender = sender.unwrap();
+        io::copy(&mut receiver, &mut sender).unwrap();
+    });
+
+    let (mut receiver, sender) = parent.split();
+    let mut sender = sender.unwrap();
+    sender.write_all(b"echo").unwrap();
+    let mut buf = [0; 4];
+    receiver.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"echo");
+
+    let (mut receiver, sender) = parent.into_split();
+    drop(sender);
+    let mut output = Vec::new();
+    receiver.read_to_end(&mut output).unwrap();
+    assert!(output.is_empty());
+    handle.join().unwrap();
+}
+
+#[test]
+fn events_with_single_token() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut parent, mut child) = new_duplex().unwrap();
+    poll.registry()
+        .register(&mut parent, PARENT, Interest::READABLE | Interest::WRITABLE)
+        .unwrap();
+    poll.registry()
+        .register(&mut child, CHILD, Interest::READABLE)
+        .unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(PARENT, Interest::WRITABLE)],
+    );
+
+    parent.write_all(b"hello").unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(CHILD, Interest::READABLE)],
+    );
+
+    // Add writable interest to the child.
+    poll.registry()
+        .reregister(&mut child, CHILD, Interest::READABLE | Interest::WRITABLE)
+        .unwrap();
+    child.write_all(b"world").unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![
+            ExpectEvent::new(PARENT, Interest::READABLE),
+            ExpectEvent::new(CHILD, Interest::WRITABLE),
+        ],
+    );
+
+    // Remove readable interest from the parent.
+    poll.registry()
+        .reregister(&mut parent, PARENT, Interest::WRITABLE)
+        .unwrap();
+    poll.registry().deregister(&mut child).unwrap();
+    child.write_all(b"!").unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    for event in events.iter() {
+        assert_eq!(event.token(), PARENT);
+        assert!(!event.is_readable(), "unexpected event: {:?}", event);
+    }
+}
+
+#[test]
+fn close_write_event() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut parent, mut child) = new_duplex().unwrap();
+    poll.registry()
+        .register(&mut child, CHILD, Interest::READABLE)
+        .unwrap();
+    parent.close_write();
+
+    poll.poll(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert!(events
+        .iter()
+        .any(|event| event.token() == CHILD && event.is_read_closed()));
+    let mut buf = [0; 8];
+    assert_eq!(child.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn partially_failed_registration() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut sender, receiver) = new_pipe().unwrap();
+    let (mut other_sender, other_receiver) = new_pipe().unwrap();
+    // Registering the sending end of the duplex pipe fails, as it's already
+    // registered.
+    poll.registry()
+        .register(&mut sender, CHILD, Interest::WRITABLE)
+        .unwrap();
+    let mut duplex = DuplexPipe::new(sender, other_receiver);
+    let err = poll
+        .registry()
+        .register(&mut duplex, PARENT, Interest::READABLE | Interest::WRITABLE)
+        .unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EEXIST));
+
+    // The receiving end was registered, which must be remembered.
+    poll.registry()
+        .reregister(&mut duplex, PARENT, Interest::READABLE)
+        .unwrap();
+    other_sender.write_all(b"hello").unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(PARENT, Interest::READABLE)],
+    );
+
+    poll.registry().deregister(&mut duplex).unwrap();
+    drop(receiver);
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "expect")]
+
+use std::io;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use mio_pipe::expect::{Expect, Match};
+use mio_pipe::pty::new_pty;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn child_stdio() {
+    let mut child = Command::new("sh")
+        .args(["-c", "echo 'name?'; read name; echo \"hello $name\""])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut expect = Expect::from_child(&mut child).unwrap();
+
+    let m = expect.expect("name?", TIMEOUT).unwrap();
+    assert_eq!(
+        m,
+        Match {
+            before: Vec::new(),
+            matched: b"name?".to_vec(),
+        }
+    );
+    expect.send_line("world", TIMEOUT).unwrap();
+    let m = expect.expect("world", TIMEOUT).unwrap();
+    assert_eq!(m.before, b"\nhello ");
+    assert_eq!(expect.expect_eof(TIMEOUT).unwrap(), b"\n");
+    assert_eq!(expect.transcript(), b"name?\nworld\nhello world\n");
+
+    let err = expect.expect("more", TIMEOUT).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn child_not_piped() {
+    let mut child = Command::new("true").stdout(Stdio::piped()).spawn().unwrap();
+    let err = Expect::from_child(&mut child).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(child.stdout.is_some());
+    child.wait().unwrap();
+}
+
+#[test]
+fn timeout() {
+    let mut child = Command::new("sh")
+        .args(["-c", "echo start; sleep 5"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut expect = Expect::from_child(&mut child).unwrap();
+
+    let err = expect
+        .expect("never", Duration::from_millis(100))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(expect.buffer(), b"start\n");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn send_timeout() {
+    // Doesn't read its input.
+    let mut child = Command::new("sleep")
+        .arg("5")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut expect = Expect::from_child(&mut child).unwrap();
+
+    // Larger than the pipe's buffer.
+    let input = vec![b'a'; 1024 * 1024];
+    let err = expect.send(&input, Duration::from_millis(100)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    // Partially written input is still recorded.
+    assert!(!expect.transcript().is_empty());
+    assert!(expect.transcript().len() < input.len());
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn pty() {
+    let (master, slave) = new_pty().unwrap();
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "read line; test -t 0 && echo \"got $line\""]);
+    slave.configure(&mut cmd).unwrap();
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+    drop(slave);
+
+    let mut expect = Expect::from_pty(master).unwrap();
+    expect.send_line("input", TIMEOUT).unwrap();
+    // Terminal echos the input.
+    expect.expect("input\r\n", TIMEOUT).unwrap();
+    expect.expect("got input\r\n", TIMEOUT).unwrap();
+    expect.expect_eof(TIMEOUT).unwrap();
+    assert!(child.wait().unwrap().success());
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_pattern() {
+    use mio_pipe::expect::Pattern;
+
+    let mut child = Command::new("sh")
+        .args(["-c", "echo 'version 1.23 installed'"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut expect = Expect::from_child(&mut child).unwrap();
+
+    let pattern = Pattern::regex(r"\d+\.\d+").unwrap();
+    let m = expect.expect(pattern, TIMEOUT).unwrap();
+    assert_eq!(m.before, b"version ");
+    assert_eq!(m.matched, b"1.23");
+    child.wait().unwrap();
+}
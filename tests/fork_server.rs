@@ -0,0 +1,106 @@
+use std::io::{Read, Write};
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::fork_server::ForkServer;
+use mio_pipe::{Receiver, Sender};
+
+mod util;
+
+use util::{expect_events, expect_one_closed_event, ExpectEvent};
+
+const SERVER: Token = Token(0);
+const WORKER: Token = Token(1);
+
+/// Worker that writes `args` followed by everything it receives.
+fn echo_worker(args: &[u8], mut receiver: Receiver, mut sender: Sender) -> i32 {
+    receiver.set_nonblocking(false).unwrap();
+    sender.set_nonblocking(false).unwrap();
+    sender.write_all(args).unwrap();
+    let mut buf = [0; 64];
+    loop {
+        match receiver.read(&mut buf) {
+            Ok(0) => return 0,
+            Ok(n) => sender.write_all(&buf[..n]).unwrap(),
+            Err(_) => return 1,
+        }
+    }
+}
+
+#[test]
+fn spawn_workers() {
+    let mut fork_server = unsafe { ForkServer::start(echo_worker) }.unwrap();
+
+    let mut worker1 = fork_server.spawn(b"1").unwrap();
+    let mut worker2 = fork_server.spawn(b"2").unwrap();
+    assert_ne!(worker1.id(), worker2.id());
+
+    for (worker, expected) in [(&mut worker1, b"1"), (&mut worker2, b"2")] {
+        worker.sender().set_nonblocking(false).unwrap();
+        worker.receiver().set_nonblocking(false).unwrap();
+        let mut buf = [0; 1];
+        worker.receiver().read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, expected);
+    }
+
+    worker1.sender().write_all(b"Hello world").unwrap();
+    let (sender, mut receiver) = worker1.into_parts();
+    drop(sender);
+    let mut output = Vec::new();
+    receiver.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"Hello world");
+}
+
+#[test]
+fn spawn_with_events() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let mut fork_server = unsafe { ForkServer::start(echo_worker) }.unwrap();
+    poll.registry()
+        .register(&mut fork_server, SERVER, Interest::READABLE)
+        .unwrap();
+    assert!(fork_server.try_response().unwrap().is_none());
+
+    let request_id = fork_server.request(b"Hello world").unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(SERVER, Interest::READABLE)],
+    );
+    let (id, worker) = fork_server.try_response().unwrap().unwrap();
+    assert_eq!(id, request_id);
+
+    let (sender, mut receiver) = worker.unwrap().into_parts();
+    poll.registry()
+        .register(&mut receiver, WORKER, Interest::READABLE)
+        .unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(WORKER, Interest::READABLE)],
+    );
+    let mut buf = [0; 20];
+    let n = receiver.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"Hello world");
+
+    // Worker exits once its input is closed.
+    drop(sender);
+    expect_one_closed_event(&mut poll, &mut events, WORKER, true);
+}
+
+#[test]
+fn stashed_responses_in_order() {
+    let mut fork_server = unsafe { ForkServer::start(echo_worker) }.unwrap();
+
+    let request1 = fork_server.request(b"1").unwrap();
+    let request2 = fork_server.request(b"2").unwrap();
+    // Reads, and stashes, the responses to the earlier requests.
+    let _worker3 = fork_server.spawn(b"3").unwrap();
+
+    let (id, _worker1) = fork_server.try_response().unwrap().unwrap();
+    assert_eq!(id, request1);
+    let (id, _worker2) = fork_server.try_response().unwrap().unwrap();
+    assert_eq!(id, request2);
+    assert!(fork_server.try_response().unwrap().is_none());
+}
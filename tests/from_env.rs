@@ -0,0 +1,82 @@
+//! Tests for `Sender::from_env` and `Receiver::from_env`.
+//!
+//! These tests modify the environment, which is not thread-safe, so they are
+//! all run from a single test.
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, IntoRawFd};
+
+use mio_pipe::{new_pipe, Receiver, Sender};
+
+const DATA1: &[u8; 11] = b"Hello world";
+
+#[test]
+fn from_env() {
+    valid_pipe();
+    wrong_pipe_end();
+    not_a_pipe();
+    invalid_variable();
+}
+
+fn valid_pipe() {
+    let (sender, receiver) = new_pipe().unwrap();
+    // Simulate a blocking, inherited file descriptor.
+    sender.set_nonblocking(false).unwrap();
+    receiver.set_nonblocking(false).unwrap();
+    env::set_var("MIO_PIPE_SENDER", sender.into_raw_fd().to_string());
+    env::set_var("MIO_PIPE_RECEIVER", receiver.into_raw_fd().to_string());
+
+    let mut sender = unsafe { Sender::from_env("MIO_PIPE_SENDER") }.unwrap();
+    let mut receiver = unsafe { Receiver::take_from_env("MIO_PIPE_RECEIVER") }.unwrap();
+    assert!(env::var_os("MIO_PIPE_SENDER").is_some());
+    assert!(env::var_os("MIO_PIPE_RECEIVER").is_none());
+    env::remove_var("MIO_PIPE_SENDER");
+
+    // Should be set to non-blocking.
+    let mut buf = [0; 20];
+    let err = receiver.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+    let n = sender.write(DATA1).unwrap();
+    assert_eq!(n, DATA1.len());
+    let n = receiver.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], DATA1);
+}
+
+fn wrong_pipe_end() {
+    let (sender, receiver) = new_pipe().unwrap();
+    env::set_var("MIO_PIPE_SENDER", sender.as_raw_fd().to_string());
+    env::set_var("MIO_PIPE_RECEIVER", receiver.as_raw_fd().to_string());
+
+    // These fail before taking ownership of the file descriptors, which are
+    // still owned by `sender` and `receiver`.
+    let err = unsafe { Receiver::from_env("MIO_PIPE_SENDER") }.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let err = unsafe { Sender::take_from_env("MIO_PIPE_RECEIVER") }.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    // Not removed on error.
+    assert!(env::var_os("MIO_PIPE_RECEIVER").is_some());
+
+    env::remove_var("MIO_PIPE_SENDER");
+    env::remove_var("MIO_PIPE_RECEIVER");
+}
+
+fn not_a_pipe() {
+    let file = File::open("Cargo.toml").unwrap();
+    env::set_var("MIO_PIPE_RECEIVER", file.as_raw_fd().to_string());
+    let err = unsafe { Receiver::from_env("MIO_PIPE_RECEIVER") }.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    env::remove_var("MIO_PIPE_RECEIVER");
+}
+
+fn invalid_variable() {
+    let err = unsafe { Receiver::from_env("MIO_PIPE_RECEIVER") }.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    env::set_var("MIO_PIPE_RECEIVER", "not a number");
+    let err = unsafe { Receiver::from_env("MIO_PIPE_RECEIVER") }.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    env::remove_var("MIO_PIPE_RECEIVER");
+}
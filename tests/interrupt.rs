@@ -0,0 +1,125 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use mio_pipe::{new_pipe, InterruptPolicy, Sender};
+
+/// Install a no-op handler for `SIGUSR1`, without `SA_RESTART`.
+fn install_handler() {
+    extern "C" fn handler(_: libc::c_int) {}
+
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = 0;
+        assert_eq!(
+            libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()),
+            0
+        );
+    }
+}
+
+/// Start reading from a blocking pipe on another thread, using `policy`.
+///
+/// Returns the sending end of the pipe, the reading thread and a flag that is
+/// set once the read returned.
+fn start_read(
+    policy: InterruptPolicy,
+) -> (
+    Sender,
+    libc::pthread_t,
+    Arc<AtomicBool>,
+    thread::JoinHandle<io::Result<usize>>,
+) {
+    install_handler();
+    let (sender, receiver) = new_pipe().unwrap();
+    receiver.set_nonblocking(false).unwrap();
+    receiver.set_interrupt_policy(policy);
+    assert_eq!(receiver.interrupt_policy(), policy);
+
+    let returned = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::spawn({
+        let returned = returned.clone();
+        move || {
+            tx.send(unsafe { libc::pthread_self() }).unwrap();
+            let res = (&receiver).read(&mut [0; 8]);
+            returned.store(true, Ordering::SeqCst);
+            res
+        }
+    });
+    (sender, rx.recv().unwrap(), returned, handle)
+}
+
+/// Interrupt the read using `policy` until it returns, which it only does if
+/// the policy doesn't retry indefinitely.
+fn interrupted_read(policy: InterruptPolicy) -> io::Result<usize> {
+    let (sender, thread, returned, handle) = start_read(policy);
+    while !returned.load(Ordering::SeqCst) {
+        assert_eq!(unsafe { libc::pthread_kill(thread, libc::SIGUSR1) }, 0);
+        thread::sleep(Duration::from_millis(1));
+    }
+    drop(sender);
+    handle.join().unwrap()
+}
+
+/// Interrupt the read using `policy` `signals` times, writing data to the
+/// pipe afterwards.
+///
+/// Signals delivered before the read started don't interrupt it, so this
+/// only ensures the read is interrupted *at most* `signals` times.
+fn read_after_signals(policy: InterruptPolicy, signals: usize) -> io::Result<usize> {
+    let (mut sender, thread, returned, handle) = start_read(policy);
+    for _ in 0..signals {
+        assert_eq!(unsafe { libc::pthread_kill(thread, libc::SIGUSR1) }, 0);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!returned.load(Ordering::SeqCst));
+    sender.write_all(b"hello").unwrap();
+    handle.join().unwrap()
+}
+
+#[test]
+fn surface() {
+    let err = interrupted_read(InterruptPolicy::Surface).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+}
+
+#[test]
+fn retry() {
+    assert_eq!(read_after_signals(InterruptPolicy::Retry, 3).unwrap(), 5);
+}
+
+#[test]
+fn retry_up_to() {
+    assert_eq!(
+        read_after_signals(InterruptPolicy::RetryUpTo(2), 2).unwrap(),
+        5
+    );
+    let err = interrupted_read(InterruptPolicy::RetryUpTo(2)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+}
+
+#[test]
+fn default_policy() {
+    let (sender, receiver) = new_pipe().unwrap();
+    assert_eq!(sender.interrupt_policy(), InterruptPolicy::Surface);
+    assert_eq!(receiver.interrupt_policy(), InterruptPolicy::default());
+    sender.set_interrupt_policy(InterruptPolicy::Retry);
+    assert_eq!(sender.interrupt_policy(), InterruptPolicy::Retry);
+}
+
+#[test]
+fn policy_normalised() {
+    let (sender, receiver) = new_pipe().unwrap();
+    assert_eq!(sender.interrupt_policy(), InterruptPolicy::Surface);
+
+    sender.set_interrupt_policy(InterruptPolicy::RetryUpTo(3));
+    assert_eq!(sender.interrupt_policy(), InterruptPolicy::RetryUpTo(3));
+    sender.set_interrupt_policy(InterruptPolicy::RetryUpTo(0));
+    assert_eq!(sender.interrupt_policy(), InterruptPolicy::Surface);
+    receiver.set_interrupt_policy(InterruptPolicy::RetryUpTo(u32::MAX));
+    assert_eq!(receiver.interrupt_policy(), InterruptPolicy::Retry);
+}
@@ -0,0 +1,81 @@
+use std::process::Command;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::jobserver::Server;
+
+mod util;
+
+use util::{expect_events, ExpectEvent};
+
+const CLIENT: Token = Token(0);
+
+#[test]
+fn acquire_and_release_tokens() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let server = Server::new(2).unwrap();
+    let mut client = server.client().unwrap();
+    poll.registry()
+        .register(&mut client, CLIENT, Interest::READABLE)
+        .unwrap();
+
+    let token1 = client.try_acquire().unwrap().unwrap();
+    let token2 = client.try_acquire().unwrap().unwrap();
+    assert!(client.try_acquire().unwrap().is_none());
+
+    drop(token1);
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(CLIENT, Interest::READABLE)],
+    );
+    let token3 = client.try_acquire().unwrap().unwrap();
+    assert!(client.try_acquire().unwrap().is_none());
+
+    drop(token2);
+    drop(token3);
+    assert!(client.try_acquire().unwrap().is_some());
+}
+
+#[test]
+fn pass_to_child_process() {
+    let server = Server::new(2).unwrap();
+
+    // Child takes a single token and doesn't return it.
+    let mut cmd = Command::new("sh");
+    cmd.env_clear()
+        .arg("-c")
+        .arg(r#"fds="${MAKEFLAGS##*--jobserver-auth=}"; head -c 1 <&"${fds%,*}" >/dev/null"#);
+    server.configure(&mut cmd);
+    let status = cmd.status().unwrap();
+    assert!(status.success());
+
+    let client = server.client().unwrap();
+    let token = client.try_acquire().unwrap();
+    assert!(token.is_some());
+    assert!(client.try_acquire().unwrap().is_none());
+}
+
+#[test]
+fn fifo() {
+    let path = std::env::temp_dir().join(format!("mio-pipe-jobserver-{}", std::process::id()));
+    let server = Server::new_fifo(&path, 1).unwrap();
+
+    let mut cmd = Command::new("sh");
+    cmd.env_clear()
+        .arg("-c")
+        .arg(r#"test -p "${MAKEFLAGS##*--jobserver-auth=fifo:}""#);
+    server.configure(&mut cmd);
+    assert!(cmd.status().unwrap().success());
+
+    let client = server.client().unwrap();
+    let token = client.try_acquire().unwrap();
+    assert!(token.is_some());
+    assert!(client.try_acquire().unwrap().is_none());
+
+    drop(client);
+    drop(server);
+    assert!(!path.exists());
+}
@@ -1,13 +1,22 @@
+// Newer Clippy versions flag `&*DATA1` in the original assertions.
+#![allow(clippy::borrow_deref_ref)]
+
+use std::env;
+use std::fs::File;
 use std::io::{self, Read, Write};
+use std::os::unix::io::{AsFd, AsRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Barrier};
 use std::thread;
-use std::time::Duration;
 
-use mio::event::Event;
 use mio::{Events, Interest, Poll, Token};
 
-use mio_pipe::{new_pipe, Receiver, Sender};
+use mio_pipe::{new_pipe, AccessMode, Receiver, Sender};
+
+mod util;
+
+use util::{assert_would_block, expect_events, expect_one_closed_event, ExpectEvent};
 
 const RECEIVER: Token = Token(0);
 const SENDER: Token = Token(1);
@@ -190,89 +199,187 @@ fn nonblocking_child_process_io() {
     child.wait().unwrap();
 }
 
-/// An event that is expected to show up when `Poll` is polled, see
-/// `expect_events`.
-#[derive(Debug)]
-pub struct ExpectEvent {
-    token: Token,
-    interests: Interest,
-}
+#[test]
+fn pipe_as_child_process_io() {
+    let (mut sender1, receiver1) = new_pipe().unwrap();
+    let (sender2, mut receiver2) = new_pipe().unwrap();
+    receiver1.set_nonblocking(false).unwrap();
+    sender2.set_nonblocking(false).unwrap();
 
-impl ExpectEvent {
-    pub const fn new(token: Token, interests: Interest) -> ExpectEvent {
-        ExpectEvent { token, interests }
-    }
+    let mut child = Command::new("cat")
+        .env_clear()
+        .stdin(receiver1)
+        .stdout(sender2)
+        .spawn()
+        .expect("failed to start `cat` command");
 
-    fn matches(&self, event: &Event) -> bool {
-        event.token() == self.token &&
-            // If we expect a readiness then also match on the event.
-            // In maths terms that is p -> q, which is the same  as !p || q.
-            (!self.interests.is_readable() || event.is_readable()) &&
-            (!self.interests.is_writable() || event.is_writable()) &&
-            (!self.interests.is_aio() || event.is_aio()) &&
-            (!self.interests.is_lio() || event.is_lio())
-    }
+    sender1.write_all(DATA1).unwrap();
+    drop(sender1);
+
+    receiver2.set_nonblocking(false).unwrap();
+    let mut buf = Vec::new();
+    receiver2.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, DATA1);
+
+    child.wait().unwrap();
 }
 
-pub fn expect_events(poll: &mut Poll, events: &mut Events, mut expected: Vec<ExpectEvent>) {
-    // In a lot of calls we expect more then one event, but it could be that
-    // poll returns the first event only in a single call. To be a bit more
-    // lenient we'll poll a couple of times.
-    for _ in 0..3 {
-        poll.poll(events, Some(Duration::from_millis(500)))
-            .expect("unable to poll");
-
-        for event in events.iter() {
-            let index = expected.iter().position(|expected| expected.matches(event));
-
-            if let Some(index) = index {
-                expected.swap_remove(index);
-            } else {
-                // Must accept sporadic events.
-                println!("got unexpected event: {:?}", event);
-            }
-        }
+#[test]
+fn owned_fd_conversion() {
+    let (sender, receiver) = new_pipe().unwrap();
+    let sender_fd = sender.as_fd().as_raw_fd();
+    let receiver_fd = receiver.as_fd().as_raw_fd();
+
+    let sender = OwnedFd::from(sender);
+    let receiver = OwnedFd::from(receiver);
+    assert_eq!(sender.as_raw_fd(), sender_fd);
+    assert_eq!(receiver.as_raw_fd(), receiver_fd);
+
+    let mut sender = Sender::from(sender);
+    let mut receiver = Receiver::from(receiver);
+    sender.write_all(DATA1).unwrap();
+    let mut buf = [0; 20];
+    let n = receiver.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], DATA1);
+}
 
-        if expected.is_empty() {
-            return;
-        }
+#[test]
+fn try_from_fd() {
+    let mut child = Command::new("cat")
+        .env_clear()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start `cat` command");
+
+    let mut sender = Sender::try_from_fd(child.stdin.take().unwrap()).unwrap();
+    let mut receiver = Receiver::try_from_fd(child.stdout.take().unwrap()).unwrap();
+    assert_would_block(receiver.read(&mut [0; 8]));
+    sender.write_all(DATA1).unwrap();
+    drop(sender);
+    receiver.set_nonblocking(false).unwrap();
+    let mut buf = Vec::new();
+    receiver.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, DATA1);
+    child.wait().unwrap();
+}
+
+#[test]
+fn try_from_fd_errors() {
+    fn assert_error<T: std::fmt::Debug>(result: io::Result<T>, msg: &str) {
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), msg);
     }
 
-    assert!(
-        expected.is_empty(),
-        "the following expected events were not found: {:?}",
-        expected
+    let (sender, receiver) = new_pipe().unwrap();
+    assert_error(
+        Receiver::try_from_fd(OwnedFd::from(sender)),
+        "file descriptor is not the read end of a pipe",
+    );
+    assert_error(
+        Sender::try_from_fd(OwnedFd::from(receiver)),
+        "file descriptor is not the write end of a pipe",
+    );
+
+    let (socket, _) = UnixStream::pair().unwrap();
+    assert_error(
+        Receiver::try_from_fd(socket),
+        "file descriptor is a socket, not a pipe",
+    );
+    let file = File::open(env::current_exe().unwrap()).unwrap();
+    assert_error(
+        Sender::try_from_fd(file),
+        "file descriptor is a regular file, not a pipe",
     );
 }
 
-/// Assert that the provided result is an `io::Error` with kind `WouldBlock`.
-pub fn assert_would_block<T>(result: io::Result<T>) {
-    match result {
-        Ok(_) => panic!("unexpected OK result, expected a `WouldBlock` error"),
-        Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {}
-        Err(err) => panic!("unexpected error result: {}", err),
-    }
+#[test]
+fn flags() {
+    let (sender, receiver) = new_pipe().unwrap();
+    let flags = sender.flags().unwrap();
+    assert_eq!(flags.access_mode(), AccessMode::Write);
+    assert!(flags.is_nonblocking());
+    assert!(flags.is_cloexec());
+    let flags = receiver.flags().unwrap();
+    assert_eq!(flags.access_mode(), AccessMode::Read);
+    assert!(flags.is_nonblocking());
+    assert!(flags.is_cloexec());
+
+    sender.set_nonblocking(false).unwrap();
+    assert!(!sender.is_nonblocking().unwrap());
+    receiver.set_cloexec(false).unwrap();
+    assert!(!receiver.is_cloexec().unwrap());
+    receiver.set_cloexec(true).unwrap();
+    assert!(receiver.is_cloexec().unwrap());
+
+    // Conversions from child process I/O leave the pipe blocking.
+    let mut child = Command::new("true")
+        .env_clear()
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start `true` command");
+    let receiver = Receiver::from(child.stdout.take().unwrap());
+    assert!(!receiver.is_nonblocking().unwrap());
+    assert_eq!(receiver.flags().unwrap().access_mode(), AccessMode::Read);
+    child.wait().unwrap();
+}
+
+#[test]
+fn close() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (sender, mut receiver) = new_pipe().unwrap();
+    poll.registry()
+        .register(&mut receiver, RECEIVER, Interest::READABLE)
+        .unwrap();
+
+    sender.close().unwrap();
+    expect_one_closed_event(&mut poll, &mut events, RECEIVER, true);
+    assert_eq!(receiver.read(&mut [0; 8]).unwrap(), 0);
+    receiver.close().unwrap();
 }
 
-/// Expected a closed event. If `read` is true is checks for `is_read_closed`,
-/// otherwise for `is_write_closed`.
-pub fn expect_one_closed_event(poll: &mut Poll, events: &mut Events, token: Token, read: bool) {
-    poll.poll(events, Some(Duration::from_secs(1))).unwrap();
-    let mut iter = events.iter();
-    let event = iter.next().unwrap();
-    assert_eq!(event.token(), token, "invalid token, event: {:#?}", event);
-    if read {
-        assert!(
-            event.is_read_closed(),
-            "expected closed or error, event: {:#?}",
-            event
-        );
-    } else {
-        assert!(
-            event.is_write_closed(),
-            "expected closed or error, event: {:#?}",
-            event
-        );
+#[test]
+fn nonblocking_emulated() {
+    let (mut sender, mut receiver) = new_pipe().unwrap();
+    sender.set_nonblocking(false).unwrap();
+    receiver.set_nonblocking(false).unwrap();
+    sender.set_nonblocking_emulated(true);
+    receiver.set_nonblocking_emulated(true);
+    assert!(sender.is_nonblocking_emulated());
+    assert!(receiver.is_nonblocking_emulated());
+
+    let mut buf = [0; 4096];
+    assert_would_block(receiver.read(&mut buf));
+    assert!(!receiver.is_nonblocking().unwrap());
+
+    // Fill the pipe, without blocking.
+    let data = [1; 8192];
+    let mut written = 0;
+    loop {
+        match sender.write(&data) {
+            Ok(n) => {
+                assert!(n <= 4096);
+                written += n;
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
     }
-    assert!(iter.next().is_none());
+    assert!(!sender.is_nonblocking().unwrap());
+
+    let mut read = 0;
+    loop {
+        match receiver.read(&mut buf) {
+            Ok(n) => read += n,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+    assert_eq!(read, written);
+
+    drop(sender);
+    assert_eq!(receiver.read(&mut buf).unwrap(), 0);
 }
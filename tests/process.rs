@@ -0,0 +1,156 @@
+use std::ffi::CStr;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::process::{Command, Stdio};
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::process::{spawn_raw, CommandExt, Stage};
+use mio_pipe::{new_pipe, Sender};
+
+mod util;
+
+use util::{expect_events, expect_one_closed_event, ExpectEvent};
+
+const RECEIVER: Token = Token(0);
+
+const DATA1: &[u8; 11] = b"Hello world";
+
+#[test]
+fn pass_fd() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut sender, child_receiver) = new_pipe().unwrap();
+    let (child_sender, mut receiver) = new_pipe().unwrap();
+    poll.registry()
+        .register(&mut receiver, RECEIVER, Interest::READABLE)
+        .unwrap();
+
+    let mut cmd = Command::new("sh");
+    cmd.env_clear()
+        .args(["-c", "cat <&3 >&4"])
+        .stdin(Stdio::null())
+        .pass_fd(3, child_receiver.into_inheritable().unwrap())
+        .pass_fd(4, child_sender.into_inheritable().unwrap());
+    let mut child = cmd.spawn().unwrap();
+    // Close our copies of the pipe ends.
+    drop(cmd);
+
+    let n = sender.write(DATA1).unwrap();
+    assert_eq!(n, DATA1.len());
+    drop(sender);
+
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(RECEIVER, Interest::READABLE)],
+    );
+    let mut buf = [0; 20];
+    let n = receiver.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], DATA1);
+
+    assert!(child.wait().unwrap().success());
+    expect_one_closed_event(&mut poll, &mut events, RECEIVER, true);
+}
+
+#[test]
+fn pass_fd_onto_each_other() {
+    // Put the sending ends on fixed file descriptors, which are passed onto
+    // each other's file descriptors.
+    let (sender1, mut receiver1) = new_pipe().unwrap();
+    let (sender2, mut receiver2) = new_pipe().unwrap();
+    let sender1 = move_to_fd(sender1, 101);
+    let sender2 = move_to_fd(sender2, 100);
+
+    let mut cmd = Command::new("sh");
+    cmd.env_clear()
+        .args(["-c", "echo one >/dev/fd/100; echo two >/dev/fd/101"])
+        .stdin(Stdio::null())
+        .pass_fd(100, sender1.into_inheritable().unwrap())
+        .pass_fd(101, sender2.into_inheritable().unwrap());
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+    assert!(child.wait().unwrap().success());
+
+    receiver1.set_nonblocking(false).unwrap();
+    receiver2.set_nonblocking(false).unwrap();
+    let mut output = String::new();
+    receiver1.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "one\n");
+    output.clear();
+    receiver2.read_to_string(&mut output).unwrap();
+    assert_eq!(output, "two\n");
+}
+
+/// Move `sender` to file descriptor `fd`.
+fn move_to_fd(sender: Sender, fd: RawFd) -> Sender {
+    assert_eq!(unsafe { libc::dup2(sender.as_raw_fd(), fd) }, fd);
+    let sender = unsafe { Sender::from_raw_fd(fd) };
+    sender.set_cloexec(true).unwrap();
+    sender
+}
+
+#[test]
+fn pipe_ends_are_not_inherited_by_default() {
+    let (sender, _receiver) = new_pipe().unwrap();
+    let fd = std::os::unix::io::AsRawFd::as_raw_fd(&sender);
+
+    let status = Command::new("sh")
+        .env_clear()
+        .arg("-c")
+        .arg(format!("true >&{}", fd))
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+fn cstr(bytes: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(bytes).unwrap()
+}
+
+#[test]
+fn spawn_raw_success() {
+    let program = cstr(b"/bin/sh\0");
+    let args = [cstr(b"sh\0"), cstr(b"-c\0"), cstr(b"exit $CODE\0")];
+    let env = [cstr(b"CODE=3\0")];
+    let mut calls = 0;
+    let mut child = unsafe {
+        spawn_raw(program, &args, Some(&env), || {
+            calls += 1;
+            Ok(())
+        })
+    }
+    .unwrap();
+    // Called in the child process, not the parent.
+    assert_eq!(calls, 0);
+    assert!(child.id() > 0);
+
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(3));
+}
+
+#[test]
+fn spawn_raw_pre_exec_error() {
+    let program = cstr(b"/bin/sh\0");
+    let args = [cstr(b"sh\0"), cstr(b"-c\0"), cstr(b"exit 0\0")];
+    let err = unsafe {
+        spawn_raw(program, &args, None, || {
+            Err(io::Error::from_raw_os_error(libc::EPERM))
+        })
+    }
+    .unwrap_err();
+    assert_eq!(err.stage(), Stage::PreExec);
+    assert_eq!(err.error().raw_os_error(), Some(libc::EPERM));
+}
+
+#[test]
+fn spawn_raw_exec_error() {
+    let program = cstr(b"/does/not/exist\0");
+    let err = unsafe { spawn_raw(program, &[program], None, || Ok(())) }.unwrap_err();
+    assert_eq!(err.stage(), Stage::Exec);
+    assert_eq!(err.error().kind(), io::ErrorKind::NotFound);
+    let err: io::Error = err.into();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
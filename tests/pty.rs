@@ -0,0 +1,90 @@
+use std::io::{Read, Write};
+use std::process::Command;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::pty::{new_pty, WindowSize};
+
+mod util;
+
+use util::{assert_would_block, expect_events, ExpectEvent};
+
+const MASTER: Token = Token(0);
+
+/// Read all output of the child from `master`.
+fn read_all(mut master: &mio_pipe::pty::PtyMaster) -> String {
+    master.set_nonblocking(false).unwrap();
+    let mut output = String::new();
+    master.read_to_string(&mut output).unwrap();
+    output
+}
+
+#[test]
+fn controlling_terminal() {
+    let (master, slave) = new_pty().unwrap();
+    assert!(slave.path().starts_with("/dev"));
+
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", "test -t 0 && test -t 1 && test -t 2 && tty"]);
+    slave.configure(&mut cmd).unwrap();
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+    let path = slave.path().to_owned();
+    drop(slave);
+
+    assert!(child.wait().unwrap().success());
+    let output = read_all(&master);
+    assert_eq!(output.trim_end(), path.to_str().unwrap());
+}
+
+#[test]
+fn window_size() {
+    let (master, slave) = new_pty().unwrap();
+    let size = WindowSize {
+        rows: 24,
+        cols: 100,
+    };
+    master.set_window_size(size).unwrap();
+    assert_eq!(master.window_size().unwrap(), size);
+
+    let mut cmd = Command::new("stty");
+    cmd.arg("size");
+    slave.configure(&mut cmd).unwrap();
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+    drop(slave);
+
+    assert!(child.wait().unwrap().success());
+    assert_eq!(read_all(&master), "24 100\r\n");
+}
+
+#[test]
+fn echo_and_raw_mode() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut master, mut slave) = new_pty().unwrap();
+    poll.registry()
+        .register(&mut master, MASTER, Interest::READABLE)
+        .unwrap();
+
+    // By default the terminal echos input.
+    master.write_all(b"hi\n").unwrap();
+    let mut buf = [0; 16];
+    let n = slave.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"hi\n");
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(MASTER, Interest::READABLE)],
+    );
+    let n = master.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"hi\r\n");
+
+    // But not in raw mode.
+    slave.set_raw_mode().unwrap();
+    master.write_all(b"hi\n").unwrap();
+    let n = slave.read(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"hi\n");
+    assert_would_block(master.read(&mut buf));
+}
@@ -0,0 +1,102 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::io::OwnedFd;
+use std::process::Command;
+use std::time::Duration;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::readiness::{Notifier, Status, Waiter};
+
+mod util;
+
+use util::{expect_events, ExpectEvent};
+
+const WAITER: Token = Token(0);
+
+#[test]
+fn service_ready() {
+    let mut waiter = Waiter::new().unwrap();
+    let mut cmd = Command::new("sh");
+    cmd.env_clear()
+        .args(["-c", r#"echo >&"$NOTIFY_FD"; sleep 1"#]);
+    waiter.configure(&mut cmd, 3).unwrap();
+    assert!(waiter.configure(&mut cmd, 4).is_err());
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+
+    let status = waiter.wait(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(status, Status::Ready);
+    assert_eq!(waiter.try_status().unwrap(), Some(Status::Ready));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn service_exited_before_ready() {
+    let mut waiter = Waiter::new().unwrap();
+    let mut cmd = Command::new("sh");
+    cmd.env_clear().args(["-c", "exit 1"]);
+    waiter.configure(&mut cmd, 3).unwrap();
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+
+    let status = waiter.wait(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(status, Status::Exited);
+    child.wait().unwrap();
+}
+
+#[test]
+fn service_timeout() {
+    let mut waiter = Waiter::new().unwrap();
+    let mut cmd = Command::new("sleep");
+    cmd.env_clear().arg("5");
+    waiter.configure(&mut cmd, 3).unwrap();
+    let mut child = cmd.spawn().unwrap();
+    drop(cmd);
+
+    let status = waiter.wait(Some(Duration::from_millis(100))).unwrap();
+    assert_eq!(status, Status::TimedOut);
+    assert_eq!(waiter.try_status().unwrap(), None);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let status = waiter.wait(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(status, Status::Exited);
+}
+
+#[test]
+fn notifier() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let mut waiter = Waiter::new().unwrap();
+    poll.registry()
+        .register(&mut waiter, WAITER, Interest::READABLE)
+        .unwrap();
+    let notifier = Notifier::from(waiter.take_sender().unwrap());
+    assert_eq!(waiter.try_status().unwrap(), None);
+
+    notifier.notify().unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(WAITER, Interest::READABLE)],
+    );
+    assert_eq!(waiter.try_status().unwrap(), Some(Status::Ready));
+}
+
+#[test]
+fn notifier_from_fd() {
+    let mut waiter = Waiter::new().unwrap();
+    let sender = waiter.take_sender().unwrap();
+    let notifier = Notifier::from_fd(OwnedFd::from(sender)).unwrap();
+    notifier.notify().unwrap();
+    let status = waiter.wait(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(status, Status::Ready);
+
+    let file = File::open("Cargo.toml").unwrap();
+    let err = Notifier::from_fd(OwnedFd::from(file)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,142 @@
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use mio::{event, Events, Interest, Poll, Registry, Token};
+
+use mio_pipe::{new_pipe, Registered};
+
+mod util;
+
+use util::{assert_would_block, expect_events, ExpectEvent};
+
+const SENDER: Token = Token(0);
+const RECEIVER: Token = Token(1);
+
+#[test]
+fn deregister_on_drop_with_duplicated_fd() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut sender, receiver) = new_pipe().unwrap();
+    let receiver =
+        Registered::new(poll.registry(), receiver, RECEIVER, Interest::READABLE).unwrap();
+    assert_eq!(receiver.token(), RECEIVER);
+    assert_eq!(receiver.interests(), Some(Interest::READABLE));
+
+    // Keep the pipe's open file description alive after the `Receiver` is
+    // dropped.
+    let dup = unsafe { libc::dup(receiver.get_ref().as_raw_fd()) };
+    assert!(dup != -1);
+    drop(receiver);
+
+    sender.write_all(b"hello").unwrap();
+    poll.poll(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty(), "unexpected events: {:?}", events);
+    unsafe { libc::close(dup) };
+}
+
+#[test]
+fn toggle_interests() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (sender, receiver) = new_pipe().unwrap();
+    let mut sender = Registered::new(poll.registry(), sender, SENDER, Interest::WRITABLE).unwrap();
+    let mut receiver =
+        Registered::new(poll.registry(), receiver, RECEIVER, Interest::READABLE).unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(SENDER, Interest::WRITABLE)],
+    );
+
+    // Removing all interests deregisters the source.
+    sender.remove_interest(Interest::WRITABLE).unwrap();
+    assert_eq!(sender.interests(), None);
+    sender.get_mut().write_all(b"hello").unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(RECEIVER, Interest::READABLE)],
+    );
+    assert!(events.iter().all(|event| event.token() == RECEIVER));
+
+    let mut buf = [0; 8];
+    assert_eq!(receiver.get_mut().read(&mut buf).unwrap(), 5);
+    assert_would_block(receiver.get_mut().read(&mut buf));
+
+    // Adding an interest registers it again.
+    sender.add_interest(Interest::WRITABLE).unwrap();
+    receiver.add_interest(Interest::READABLE).unwrap();
+    assert_eq!(sender.interests(), Some(Interest::WRITABLE));
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(SENDER, Interest::WRITABLE)],
+    );
+
+    let sender = sender.deregister().unwrap();
+    drop(sender);
+    drop(receiver);
+}
+
+/// Event source of which deregistering fails if `fail_deregister` is set.
+struct FailingSource {
+    sender: mio_pipe::Sender,
+    fail_deregister: bool,
+}
+
+impl event::Source for FailingSource {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.sender.register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        self.sender.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        if self.fail_deregister {
+            return Err(io::Error::other("deregister failed"));
+        }
+        self.sender.deregister(registry)
+    }
+}
+
+#[test]
+fn deregister_error_returns_source() {
+    let poll = Poll::new().unwrap();
+    let (sender, _receiver) = new_pipe().unwrap();
+    let source = FailingSource {
+        sender,
+        fail_deregister: true,
+    };
+
+    let registered = Registered::new(poll.registry(), source, SENDER, Interest::WRITABLE).unwrap();
+    let (err, mut registered) = match registered.deregister() {
+        Ok(_) => panic!("unexpected success deregistering"),
+        Err(err) => err,
+    };
+    assert_eq!(err.to_string(), "deregister failed");
+    // Still registered.
+    assert_eq!(registered.interests(), Some(Interest::WRITABLE));
+
+    registered.get_mut().fail_deregister = false;
+    let source = match registered.deregister() {
+        Ok(source) => source,
+        Err((err, _)) => panic!("unexpected error deregistering: {}", err),
+    };
+    assert!(!source.fail_deregister);
+}
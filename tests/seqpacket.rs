@@ -0,0 +1,146 @@
+#![cfg(any(target_os = "android", target_os = "linux"))]
+
+use std::io::{self, IoSlice, Read, Write};
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::time::Duration;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::seqpacket::{new_seqpacket_pair, Receiver, Sender};
+
+mod util;
+
+use util::{assert_would_block, expect_events, ExpectEvent};
+
+const SENDER: Token = Token(0);
+const RECEIVER: Token = Token(1);
+
+#[test]
+fn message_boundaries_beyond_pipe_buf() {
+    let (mut sender, mut receiver) = new_seqpacket_pair().unwrap();
+    let large = vec![1; libc::PIPE_BUF * 4];
+    assert_eq!(sender.write(&large).unwrap(), large.len());
+    sender.write_all(b"small").unwrap();
+    let bufs = [IoSlice::new(b"vec"), IoSlice::new(b"tored")];
+    assert_eq!(sender.write_vectored(&bufs).unwrap(), 8);
+
+    let mut buf = vec![0; large.len() * 2];
+    assert_eq!(receiver.next_message_len().unwrap(), large.len());
+    assert_eq!(receiver.read(&mut buf).unwrap(), large.len());
+    assert_eq!(&buf[..large.len()], &*large);
+    assert_eq!(receiver.read(&mut buf).unwrap(), 5);
+    assert_eq!(&buf[..5], b"small");
+    assert_eq!(receiver.read(&mut buf).unwrap(), 8);
+    assert_eq!(&buf[..8], b"vectored");
+    assert_would_block(receiver.read(&mut buf));
+}
+
+#[test]
+fn truncated_message() {
+    let (mut sender, mut receiver) = new_seqpacket_pair().unwrap();
+    sender.write_all(b"hello world").unwrap();
+    sender.write_all(b"next").unwrap();
+
+    let mut buf = [0; 5];
+    let err = receiver.read(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    // Remainder of the message is discarded.
+    assert_eq!(receiver.read(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], b"next");
+}
+
+#[test]
+fn sender_shutdown() {
+    let (mut sender, mut receiver) = new_seqpacket_pair().unwrap();
+    let dup = sender.try_clone().unwrap();
+    sender.write_all(b"hello").unwrap();
+    sender.shutdown().unwrap();
+
+    // Shutting down affects duplicates.
+    let err = (&dup).write(b"hello").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+    let mut buf = [0; 8];
+    assert_eq!(receiver.read(&mut buf).unwrap(), 5);
+    assert_eq!(receiver.next_message_len().unwrap(), 0);
+    assert_eq!(receiver.read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn receiver_shutdown_and_drop() {
+    let (mut sender, receiver) = new_seqpacket_pair().unwrap();
+    receiver.shutdown().unwrap();
+    let err = sender.write(b"hello").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+    let (mut sender, receiver) = new_seqpacket_pair().unwrap();
+    drop(receiver);
+    let err = sender.write(b"hello").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn events() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let (mut sender, mut receiver) = new_seqpacket_pair().unwrap();
+    poll.registry()
+        .register(&mut sender, SENDER, Interest::WRITABLE)
+        .unwrap();
+    poll.registry()
+        .register(&mut receiver, RECEIVER, Interest::READABLE)
+        .unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(SENDER, Interest::WRITABLE)],
+    );
+
+    sender.write_all(b"hello").unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(RECEIVER, Interest::READABLE)],
+    );
+    let mut buf = [0; 8];
+    assert_eq!(receiver.read(&mut buf).unwrap(), 5);
+
+    drop(sender);
+    poll.poll(&mut events, Some(Duration::from_secs(1)))
+        .unwrap();
+    assert!(events
+        .iter()
+        .any(|event| event.token() == RECEIVER && event.is_read_closed()));
+}
+
+#[test]
+fn fd_conversions() {
+    let (sender, receiver) = new_seqpacket_pair().unwrap();
+    let sender_fd = sender.as_raw_fd();
+    let fd = OwnedFd::from(sender);
+    assert_eq!(fd.as_raw_fd(), sender_fd);
+    let mut sender = Sender::from(fd);
+
+    let mut receiver = Receiver::from(OwnedFd::from(receiver));
+    receiver.set_nonblocking(false).unwrap();
+    sender.write_all(b"hello").unwrap();
+    let mut buf = [0; 8];
+    assert_eq!(receiver.read(&mut buf).unwrap(), 5);
+}
+
+#[test]
+fn empty_write() {
+    let (mut sender, mut receiver) = new_seqpacket_pair().unwrap();
+    assert_eq!(sender.write(&[]).unwrap(), 0);
+    let bufs = [IoSlice::new(&[]), IoSlice::new(&[])];
+    assert_eq!(sender.write_vectored(&bufs).unwrap(), 0);
+    sender.write_all(b"after").unwrap();
+    drop(sender);
+
+    // No empty message, which would be read as end of file.
+    let mut output = Vec::new();
+    receiver.set_nonblocking(false).unwrap();
+    receiver.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"after");
+}
@@ -0,0 +1,131 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::process::{self, Command, Stdio};
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::stdio::{self, FileType, StdioError};
+
+mod util;
+
+use util::{assert_would_block, expect_events, ExpectEvent};
+
+/// Environment variable used to select the test in `stdio_process`.
+const MODE: &str = "MIO_PIPE_STDIO_TEST";
+
+/// Returns `true` if `fd` is in non-blocking mode.
+fn is_nonblocking(fd: i32) -> bool {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    assert!(flags != -1);
+    flags & libc::O_NONBLOCK != 0
+}
+
+/// Not an actual test, run as a separate process by the tests below.
+#[test]
+fn stdio_process() {
+    let mode = match env::var(MODE) {
+        Ok(mode) => mode,
+        Err(_) => return,
+    };
+
+    match mode.as_str() {
+        "stdin" => {
+            let mut stdin = stdio::stdin().unwrap();
+            assert_eq!(stdin.file_type(), FileType::Pipe);
+            assert_would_block(stdin.read(&mut [0; 8]));
+            assert!(!is_nonblocking(libc::STDIN_FILENO));
+
+            let mut poll = Poll::new().unwrap();
+            let mut events = Events::with_capacity(8);
+            poll.registry()
+                .register(&mut stdin, Token(0), Interest::READABLE)
+                .unwrap();
+            // Signal the parent to write something.
+            io::stderr().write_all(b"ready\n").unwrap();
+            expect_events(
+                &mut poll,
+                &mut events,
+                vec![ExpectEvent::new(Token(0), Interest::READABLE)],
+            );
+            let mut buf = [0; 8];
+            let n = stdin.read(&mut buf).unwrap();
+            assert_eq!(&buf[..n], b"hello");
+        }
+        "stdout" => {
+            let mut stdout = stdio::stdout().unwrap();
+            assert_eq!(stdout.file_type(), FileType::Pipe);
+            stdout.write_all(b"#hello#").unwrap();
+            assert!(!is_nonblocking(libc::STDOUT_FILENO));
+        }
+        "stderr_socket" => {
+            let stderr = stdio::stderr().unwrap();
+            assert_eq!(stderr.file_type(), FileType::Socket);
+            // Can't reopen sockets, so the flags are changed and restored on
+            // drop.
+            assert!(is_nonblocking(libc::STDERR_FILENO));
+            drop(stderr);
+            assert!(!is_nonblocking(libc::STDERR_FILENO));
+        }
+        "stdin_file" => match stdio::stdin() {
+            Err(StdioError::Unsupported(FileType::File)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        },
+        _ => panic!("unknown mode"),
+    }
+    process::exit(0);
+}
+
+fn command(mode: &str) -> Command {
+    let mut cmd = Command::new(env::current_exe().unwrap());
+    cmd.args([
+        "--exact",
+        "stdio_process",
+        "--nocapture",
+        "--test-threads=1",
+    ])
+    .env(MODE, mode);
+    cmd
+}
+
+#[test]
+fn stdin_pipe() {
+    let mut child = command("stdin")
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let mut buf = [0; 6];
+    stderr.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ready\n");
+    child.stdin.as_mut().unwrap().write_all(b"hello").unwrap();
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn stdout_pipe() {
+    let output = command("stdout").output().unwrap();
+    assert!(output.status.success(), "output: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("#hello#"), "stdout: {}", stdout);
+}
+
+#[test]
+fn stderr_socket() {
+    let (socket, _peer) = UnixStream::pair().unwrap();
+    let output = command("stderr_socket")
+        .stderr(Stdio::from(OwnedFd::from(socket)))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "output: {:?}", output);
+}
+
+#[test]
+fn stdin_regular_file() {
+    let file = File::open(env::current_exe().unwrap()).unwrap();
+    let output = command("stdin_file").stdin(file).output().unwrap();
+    assert!(output.status.success(), "output: {:?}", output);
+}
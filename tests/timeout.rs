@@ -0,0 +1,145 @@
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mio_pipe::{new_pipe, wait_any};
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+#[test]
+fn wait_readable() {
+    let (mut sender, receiver) = new_pipe().unwrap();
+    assert!(!receiver.wait_readable(TIMEOUT).unwrap());
+
+    sender.write_all(b"hello").unwrap();
+    assert!(receiver.wait_readable(TIMEOUT).unwrap());
+
+    // Reading all data makes it not readable again.
+    let mut buf = [0; 8];
+    assert_eq!((&receiver).read(&mut buf).unwrap(), 5);
+    assert!(!receiver.wait_readable(TIMEOUT).unwrap());
+
+    // Readable once the sender is dropped, reading returns end of file.
+    drop(sender);
+    assert!(receiver.wait_readable(TIMEOUT).unwrap());
+    assert_eq!((&receiver).read(&mut buf).unwrap(), 0);
+}
+
+#[test]
+fn wait_writable() {
+    let (sender, mut receiver) = new_pipe().unwrap();
+    assert!(sender.wait_writable(TIMEOUT).unwrap());
+
+    fill(&sender);
+    assert!(!sender.wait_writable(TIMEOUT).unwrap());
+
+    let mut buf = vec![0; 1 << 16];
+    let _ = receiver.read(&mut buf).unwrap();
+    assert!(sender.wait_writable(TIMEOUT).unwrap());
+}
+
+#[test]
+fn read_timeout() {
+    let (sender, receiver) = new_pipe().unwrap();
+    let mut buf = [0; 8];
+
+    let start = Instant::now();
+    let err = receiver.read_timeout(&mut buf, TIMEOUT).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= TIMEOUT);
+
+    let handle = thread::spawn(move || {
+        thread::sleep(TIMEOUT);
+        (&sender).write_all(b"hello").unwrap();
+    });
+    let n = receiver
+        .read_timeout(&mut buf, Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(&buf[..n], b"hello");
+    handle.join().unwrap();
+
+    // End of file.
+    let n = receiver.read_timeout(&mut buf, TIMEOUT).unwrap();
+    assert_eq!(n, 0);
+}
+
+#[test]
+fn read_timeout_blocking() {
+    let (sender, receiver) = new_pipe().unwrap();
+    receiver.set_nonblocking(false).unwrap();
+    let mut buf = [0; 8];
+
+    let err = receiver.read_timeout(&mut buf, TIMEOUT).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    (&sender).write_all(b"hello").unwrap();
+    let n = receiver.read_timeout(&mut buf, TIMEOUT).unwrap();
+    assert_eq!(&buf[..n], b"hello");
+}
+
+#[test]
+fn write_timeout() {
+    let (sender, mut receiver) = new_pipe().unwrap();
+    assert_eq!(sender.write_timeout(b"hello", TIMEOUT).unwrap(), 5);
+
+    fill(&sender);
+    let err = sender.write_timeout(b"hello", TIMEOUT).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    let mut buf = vec![0; 1 << 16];
+    let _ = receiver.read(&mut buf).unwrap();
+    assert_eq!(sender.write_timeout(b"hello", TIMEOUT).unwrap(), 5);
+}
+
+#[test]
+fn write_timeout_blocking() {
+    let (sender, mut receiver) = new_pipe().unwrap();
+    fill(&sender);
+    sender.set_nonblocking(false).unwrap();
+
+    let err = sender.write_timeout(b"hello", TIMEOUT).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    // Drain the pipe, after which a large write must not block.
+    receiver.set_nonblocking(false).unwrap();
+    let handle = thread::spawn(move || {
+        let mut buf = Vec::new();
+        receiver.read_to_end(&mut buf).unwrap();
+        buf.len()
+    });
+    let data = vec![0; 1 << 20];
+    let n = sender.write_timeout(&data, Duration::from_secs(5)).unwrap();
+    assert!(n > 0 && n <= libc::PIPE_BUF);
+    drop(sender);
+    assert!(handle.join().unwrap() > n);
+}
+
+#[test]
+fn wait_any_pipe_ends() {
+    let (sender1, receiver1) = new_pipe().unwrap();
+    let (sender2, receiver2) = new_pipe().unwrap();
+    fill(&sender2);
+
+    let ends: [&dyn AsRawFd; 2] = [&receiver1, &sender2];
+    assert!(wait_any(&ends, TIMEOUT).unwrap().is_empty());
+
+    (&sender1).write_all(b"hello").unwrap();
+    assert_eq!(wait_any(&ends, TIMEOUT).unwrap(), vec![0]);
+
+    // Receiving end is readable, sending end is writable.
+    let ends: [&dyn AsRawFd; 3] = [&receiver1, &receiver2, &sender1];
+    assert_eq!(wait_any(&ends, TIMEOUT).unwrap(), vec![0, 1, 2]);
+}
+
+/// Fill the pipe of `sender`, which must be in non-blocking mode.
+fn fill(mut sender: &mio_pipe::Sender) {
+    let buf = [0; 4096];
+    loop {
+        match sender.write(&buf) {
+            Ok(_) => continue,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::Duration;
+
+use mio::{Events, Interest, Poll, Token};
+
+use mio_pipe::waker::{PipeWaker, WakeReasons};
+
+mod util;
+
+use util::{expect_events, ExpectEvent};
+
+const WAKER1: Token = Token(0);
+const WAKER2: Token = Token(1);
+
+#[test]
+fn smoke() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let mut waker = PipeWaker::new().unwrap();
+    poll.registry()
+        .register(&mut waker, WAKER1, Interest::READABLE)
+        .unwrap();
+
+    waker.wake().unwrap();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(WAKER1, Interest::READABLE)],
+    );
+    assert_eq!(waker.drain().unwrap(), WakeReasons::default());
+
+    // No more events after draining.
+    poll.poll(&mut events, Some(Duration::from_millis(100)))
+        .unwrap();
+    assert!(events.is_empty());
+}
+
+#[test]
+fn wake_reasons_are_coalesced() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let mut waker = PipeWaker::new().unwrap();
+    poll.registry()
+        .register(&mut waker, WAKER1, Interest::READABLE)
+        .unwrap();
+
+    waker.wake_with_reason(1).unwrap();
+    waker.wake_with_reason(5).unwrap();
+    waker.wake_with_reason(1).unwrap();
+    waker.wake_with_reason(63).unwrap();
+    assert!(waker.wake_with_reason(64).is_err());
+
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![ExpectEvent::new(WAKER1, Interest::READABLE)],
+    );
+    let reasons = waker.drain().unwrap();
+    assert!(!reasons.is_empty());
+    assert_eq!(reasons.iter().collect::<Vec<_>>(), vec![1, 5, 63]);
+    assert!(waker.drain().unwrap().is_empty());
+}
+
+#[test]
+fn multiple_wakers_from_other_threads() {
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let mut waker1 = PipeWaker::new().unwrap();
+    let mut waker2 = PipeWaker::new().unwrap();
+    poll.registry()
+        .register(&mut waker1, WAKER1, Interest::READABLE)
+        .unwrap();
+    poll.registry()
+        .register(&mut waker2, WAKER2, Interest::READABLE)
+        .unwrap();
+    let waker1 = Arc::new(waker1);
+    let waker2 = Arc::new(waker2);
+
+    let barrier = Arc::new(Barrier::new(2));
+    let thread_barrier = barrier.clone();
+    let thread_waker1 = waker1.clone();
+    let thread_waker2 = waker2.clone();
+    let handle = thread::spawn(move || {
+        thread_waker1.wake_with_reason(2).unwrap();
+        thread_waker2.wake_with_reason(3).unwrap();
+        thread_barrier.wait();
+    });
+
+    barrier.wait();
+    expect_events(
+        &mut poll,
+        &mut events,
+        vec![
+            ExpectEvent::new(WAKER1, Interest::READABLE),
+            ExpectEvent::new(WAKER2, Interest::READABLE),
+        ],
+    );
+    assert_eq!(waker1.drain().unwrap().iter().collect::<Vec<_>>(), vec![2]);
+    assert_eq!(waker2.drain().unwrap().iter().collect::<Vec<_>>(), vec![3]);
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn no_lost_wake_ups() {
+    const THREADS: usize = 4;
+    const ITERATIONS: usize = 50_000;
+
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(8);
+
+    let mut waker = PipeWaker::new().unwrap();
+    poll.registry()
+        .register(&mut waker, WAKER1, Interest::READABLE)
+        .unwrap();
+    let waker = Arc::new(waker);
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|i| {
+            let waker = waker.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    if i % 2 == 0 {
+                        waker.wake().unwrap();
+                    } else {
+                        waker.wake_with_reason(i as u8).unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for _ in 0..ITERATIONS {
+        poll.poll(&mut events, Some(Duration::from_secs(1)))
+            .unwrap();
+        if events.is_empty() {
+            stop.store(true, Ordering::Relaxed);
+            panic!("lost wake up, poll timed out");
+        }
+        let _ = waker.drain().unwrap();
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}